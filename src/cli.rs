@@ -0,0 +1,570 @@
+use std::{collections::HashSet, error::Error, fs, io::Read, path::Path, process::ExitCode};
+
+use crate::{
+    config::Config, database::DB, formats::{Format, ImportMode, csv::{self, ColumnMapping}},
+    query::Query, services::task_service::TaskService, task::*
+};
+
+
+pub const USAGE: &str = "Usage:
+  todolist                                  Open the interactive interface
+  todolist add <name> [--parent ID] [--desc TEXT] [--due DATE] [--priority LEVEL]
+                      [--tags a,b] [--repeat RULE]
+  todolist list [--completed | --open] [--tree] [--filter QUERY]
+  todolist done <id>
+  todolist edit <id> [--name TEXT] [--desc TEXT|-] [--due DATE|-] [--priority LEVEL]
+                     [--repeat RULE|-] [--parent ID|root]
+  todolist rm <id>
+  todolist history <id>
+  todolist archive <id> | --all
+  todolist unarchive <id>
+  todolist undo | redo
+  todolist export [--format FORMAT] [--output FILE]
+  todolist import <FILE|-> [--format FORMAT] [--replace] [--columns FIELD=HEADER,...]
+  todolist help
+
+Formats: json, todotxt, markdown, ical, csv, taskwarrior, org. Without --format it is guessed
+from the file extension.
+import merges into the current list with new ids, updating tasks already imported from
+JSON, Taskwarrior, Org or iCalendar files; --replace moves the tasks in the list to the
+trash first.
+archive moves a completed task and its subtasks out of the list; --all archives every
+completed top level task.
+--columns maps CSV headers to fields, e.g. name=Title,due_date=Deadline.
+--filter takes a query like 'status:open tag:work due<7d depth<=2 \"deploy\"'; with --tree
+the parents of matching tasks are listed too.
+
+Exit codes: 0 success, 1 error, 2 invalid usage";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Add {
+        name: String,
+        parent_id: Option<u32>,
+        description: Option<String>,
+        due_date: Option<DueDate>,
+        priority: Priority,
+        tags: Vec<String>,
+        recurrence: Option<Recurrence>,
+    },
+    List {
+        completed: Option<bool>,
+        tree: bool,
+        filter: Option<Query>,
+    },
+    Done {
+        id: u32,
+    },
+    Edit {
+        id: u32,
+        name: Option<String>,
+        description: Option<Option<String>>,
+        due_date: Option<Option<DueDate>>,
+        priority: Option<Priority>,
+        recurrence: Option<Option<Recurrence>>,
+        parent_id: Option<Option<u32>>,
+    },
+    Remove {
+        id: u32,
+    },
+    History {
+        id: u32,
+    },
+    /// Archives one task, or every completed top level task when `id` is `None`.
+    Archive {
+        id: Option<u32>,
+    },
+    Unarchive {
+        id: u32,
+    },
+    Undo,
+    Redo,
+    Export {
+        format: Option<Format>,
+        output: Option<String>,
+    },
+    Import {
+        path: String,
+        format: Option<Format>,
+        replace: bool,
+        columns: Option<ColumnMapping>,
+    },
+    Help,
+}
+
+/// Splits arguments into positionals and `--option value` / `--option=value` pairs.
+/// Options listed in `switches` take no value.
+struct ParsedArgs {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+}
+
+impl ParsedArgs {
+    fn parse(args: &[String], switches: &[&str]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut options = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.strip_prefix("--") {
+                Some(option) => {
+                    let (key, value) = match option.split_once('=') {
+                        Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                        None if switches.contains(&option) => (option.to_string(), None),
+                        None => {
+                            let value = iter.next().ok_or(format!("Missing value for --{}", option))?;
+                            (option.to_string(), Some(value.clone()))
+                        }
+                    };
+                    options.push((key, value));
+                }
+                None => positional.push(arg.clone()),
+            }
+        }
+        Ok(Self { positional, options })
+    }
+
+    fn check_options(&self, allowed: &[&str]) -> Result<(), String> {
+        match self.options.iter().find(|(key, _)| !allowed.contains(&key.as_str())) {
+            Some((key, _)) => Err(format!("Unknown option --{}", key)),
+            None => Ok(()),
+        }
+    }
+
+    fn value(&self, key: &str) -> Option<&str> {
+        self.options.iter().rev()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    fn switch(&self, key: &str) -> bool {
+        self.options.iter().any(|(k, _)| k == key)
+    }
+
+    fn single_positional(&self, what: &str) -> Result<&str, String> {
+        match self.positional.as_slice() {
+            [value] => Ok(value),
+            [] => Err(format!("Missing {}", what)),
+            _ => Err(format!("Expected a single {}", what)),
+        }
+    }
+
+    fn id(&self) -> Result<u32, String> {
+        parse_id(self.single_positional("task id")?)
+    }
+}
+
+fn parse_id(input: &str) -> Result<u32, String> {
+    input.parse().map_err(|_| format!("Invalid task id '{}'", input))
+}
+
+fn parse_name(input: &str) -> Result<String, String> {
+    if input.trim().is_empty() {
+        return Err(String::from("Task name can't be empty"));
+    }
+    Ok(input.to_string())
+}
+
+fn parse_format(input: Option<&str>) -> Result<Option<Format>, String> {
+    input.map(Format::parse).transpose().map_err(|e| e.to_string())
+}
+
+/// The explicit format, else the one matching the file extension, else JSON.
+fn resolve_format(format: Option<Format>, path: Option<&str>) -> Format {
+    format
+        .or_else(|| path.and_then(|p| Format::from_path(Path::new(p))))
+        .unwrap_or(Format::Json)
+}
+
+/// Parses an optional value, where `-` means "clear".
+fn parse_clearable<T>(
+    input: Option<&str>,
+    parse: impl Fn(&str) -> Result<T, Box<dyn Error>>,
+) -> Result<Option<Option<T>>, String> {
+    match input {
+        Some("-") => Ok(Some(None)),
+        Some(value) => parse(value).map(|v| Some(Some(v))).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+impl Command {
+    /// Parses the arguments following the program name. Errors are usage messages.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let (command, rest) = args.split_first().ok_or("Missing command")?;
+        match command.as_str() {
+            "add" => {
+                let parsed = ParsedArgs::parse(rest, &[])?;
+                parsed.check_options(&["parent", "desc", "due", "priority", "tags", "repeat"])?;
+                Ok(Command::Add {
+                    name: parse_name(parsed.single_positional("task name")?)?,
+                    parent_id: parsed.value("parent").map(parse_id).transpose()?,
+                    description: parsed.value("desc").map(str::to_string),
+                    due_date: parsed.value("due").map(DueDate::parse).transpose().map_err(|e| e.to_string())?,
+                    priority: parsed.value("priority").map(Priority::parse).transpose()
+                        .map_err(|e| e.to_string())?.unwrap_or_default(),
+                    tags: parsed.value("tags").map(Tag::parse_list).transpose()
+                        .map_err(|e| e.to_string())?.unwrap_or_default(),
+                    recurrence: parsed.value("repeat").map(Recurrence::parse).transpose()
+                        .map_err(|e| e.to_string())?,
+                })
+            }
+            "list" | "ls" => {
+                let parsed = ParsedArgs::parse(rest, &["completed", "open", "tree"])?;
+                parsed.check_options(&["completed", "open", "tree", "filter"])?;
+                if !parsed.positional.is_empty() {
+                    return Err(String::from("list takes no arguments"));
+                }
+                let completed = match (parsed.switch("completed"), parsed.switch("open")) {
+                    (true, true) => return Err(String::from("--completed and --open are exclusive")),
+                    (true, false) => Some(true),
+                    (false, true) => Some(false),
+                    (false, false) => None,
+                };
+                Ok(Command::List {
+                    completed,
+                    tree: parsed.switch("tree"),
+                    filter: parsed.value("filter").map(Query::parse).transpose().map_err(|e| e.to_string())?,
+                })
+            }
+            "done" => {
+                let parsed = ParsedArgs::parse(rest, &[])?;
+                parsed.check_options(&[])?;
+                Ok(Command::Done { id: parsed.id()? })
+            }
+            "edit" => {
+                let parsed = ParsedArgs::parse(rest, &[])?;
+                parsed.check_options(&["name", "desc", "due", "priority", "repeat", "parent"])?;
+                let parent_id = match parsed.value("parent") {
+                    Some("root") => Some(None),
+                    Some(value) => Some(Some(parse_id(value)?)),
+                    None => None,
+                };
+                let id = parsed.id()?;
+                if parsed.options.is_empty() {
+                    return Err(String::from("edit needs at least one of --name, --desc, --due, --priority, --repeat or --parent"));
+                }
+                Ok(Command::Edit {
+                    id,
+                    name: parsed.value("name").map(parse_name).transpose()?,
+                    description: parse_clearable(parsed.value("desc"), |value| Ok(value.to_string()))?,
+                    due_date: parse_clearable(parsed.value("due"), DueDate::parse)?,
+                    priority: parsed.value("priority").map(Priority::parse).transpose()
+                        .map_err(|e| e.to_string())?,
+                    recurrence: parse_clearable(parsed.value("repeat"), Recurrence::parse)?,
+                    parent_id,
+                })
+            }
+            "rm" | "delete" => {
+                let parsed = ParsedArgs::parse(rest, &[])?;
+                parsed.check_options(&[])?;
+                Ok(Command::Remove { id: parsed.id()? })
+            }
+            "history" | "log" => {
+                let parsed = ParsedArgs::parse(rest, &[])?;
+                parsed.check_options(&[])?;
+                Ok(Command::History { id: parsed.id()? })
+            }
+            "archive" => {
+                let parsed = ParsedArgs::parse(rest, &["all"])?;
+                parsed.check_options(&["all"])?;
+                match (parsed.switch("all"), parsed.positional.is_empty()) {
+                    (true, true) => Ok(Command::Archive { id: None }),
+                    (true, false) => Err(String::from("--all takes no task id")),
+                    (false, _) => Ok(Command::Archive { id: Some(parsed.id()?) }),
+                }
+            }
+            "unarchive" => {
+                let parsed = ParsedArgs::parse(rest, &[])?;
+                parsed.check_options(&[])?;
+                Ok(Command::Unarchive { id: parsed.id()? })
+            }
+            "undo" | "redo" => {
+                let parsed = ParsedArgs::parse(rest, &[])?;
+                parsed.check_options(&[])?;
+                if !parsed.positional.is_empty() {
+                    return Err(format!("{} takes no arguments", command));
+                }
+                Ok(if command == "undo" { Command::Undo } else { Command::Redo })
+            }
+            "export" => {
+                let parsed = ParsedArgs::parse(rest, &[])?;
+                parsed.check_options(&["format", "output"])?;
+                if !parsed.positional.is_empty() {
+                    return Err(String::from("export takes no arguments, use --output FILE"));
+                }
+                Ok(Command::Export {
+                    format: parse_format(parsed.value("format"))?,
+                    output: parsed.value("output").map(str::to_string),
+                })
+            }
+            "import" => {
+                let parsed = ParsedArgs::parse(rest, &["replace"])?;
+                parsed.check_options(&["format", "replace", "columns"])?;
+                Ok(Command::Import {
+                    path: parsed.single_positional("file to import")?.to_string(),
+                    format: parse_format(parsed.value("format"))?,
+                    replace: parsed.switch("replace"),
+                    columns: parsed.value("columns").map(ColumnMapping::parse).transpose()
+                        .map_err(|e| e.to_string())?,
+                })
+            }
+            "help" | "--help" | "-h" => Ok(Command::Help),
+            other => Err(format!("Unknown command '{}'", other)),
+        }
+    }
+}
+
+/// Runs a subcommand and maps the outcome to the process exit code.
+pub fn run(args: &[String]) -> ExitCode {
+    let command = match Command::parse(args) {
+        Ok(command) => command,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match execute(command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn execute(command: Command) -> Result<(), Box<dyn Error>> {
+    if command == Command::Help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let service = TaskService::new(DB::new(&Config::build()?)?);
+
+    match command {
+        Command::Add { name, parent_id, description, due_date, priority, tags, recurrence } => {
+            if let Some(parent_id) = parent_id {
+                service.get_task_by_id(parent_id)?;
+            }
+            let id = service.add_task(CreateTask {
+                name, parent_id, description, due_date, priority, recurrence, ..Default::default()
+            }, &tags)?;
+            println!("{}", id);
+        }
+        Command::List { completed, tree, filter } => {
+            let matching: Option<HashSet<u32>> = match filter {
+                Some(query) => Some(service.find_tasks(&query)?.iter().map(|task| task.id).collect()),
+                None => None,
+            };
+            let filter = |task: &Task| completed.is_none_or(|c| task.completed == c)
+                && matching.as_ref().is_none_or(|ids| ids.contains(&task.id));
+            if tree {
+                let tasks = TaskWithKids::filter(service.load_hierarchy()?, &filter);
+                print_tree(&tasks, 0);
+            } else {
+                let tasks = service.load_hierarchy()?;
+                print_flat(&tasks, &filter);
+            }
+        }
+        Command::Done { id } => {
+            let task = service.get_task_by_id(id)?;
+            if task.completed {
+                println!("Task {} is already completed", id);
+            } else if let Some(next_id) = service.toggle_task_completion(id)? {
+                println!("Next occurrence: {}", next_id);
+            }
+        }
+        Command::Edit { id, name, description, due_date, priority, recurrence, parent_id } => {
+            service.get_task_by_id(id)?;
+            service.edit_task(UpdateTask {
+                id, name, description, due_date, priority, recurrence, parent_id, ..Default::default()
+            })?;
+        }
+        Command::Remove { id } => {
+            service.get_task_by_id(id)?;
+            service.delete_task(id)?;
+        }
+        Command::History { id } => {
+            let history = service.history(id)?;
+            if history.is_empty() {
+                println!("No history for task {}", id);
+            }
+            for event in history {
+                println!("{}  {}", utc_to_local(event.created_at).format("%Y-%m-%d %H:%M:%S"), event);
+            }
+        }
+        Command::Archive { id: Some(id) } => service.archive_task(id)?,
+        Command::Archive { id: None } => {
+            println!("Archived {} tasks", service.archive_completed_roots()?);
+        }
+        Command::Unarchive { id } => service.unarchive_task(id)?,
+        Command::Undo => match service.undo()? {
+            Some(label) => println!("Undone: {}", label),
+            None => println!("Nothing to undo"),
+        },
+        Command::Redo => match service.redo()? {
+            Some(label) => println!("Redone: {}", label),
+            None => println!("Nothing to redo"),
+        },
+        Command::Export { format, output } => {
+            let exported = service.export(resolve_format(format, output.as_deref()))?;
+            match output {
+                Some(path) => fs::write(path, exported)?,
+                None => println!("{}", exported),
+            }
+        }
+        Command::Import { path, format, replace, columns } => {
+            let input = if path == "-" {
+                let mut input = String::new();
+                std::io::stdin().read_to_string(&mut input)?;
+                input
+            } else {
+                fs::read_to_string(&path)?
+            };
+            let mode = if replace { ImportMode::Replace } else { ImportMode::Merge };
+            let count = match (resolve_format(format, Some(&path)), columns) {
+                (Format::Csv, Some(columns)) => {
+                    service.import_tasks(&csv::import_with(&input, &columns)?, mode)?
+                }
+                (_, Some(_)) => return Err("--columns only applies to CSV imports".into()),
+                (format, None) => service.import(format, &input, mode)?,
+            };
+            println!("Imported {} tasks", count);
+        }
+        Command::Help => {}
+    }
+    Ok(())
+}
+
+fn format_task_line(task: &Task) -> String {
+    let mut line = format!("[{}] {}", if task.completed { "x" } else { " " }, task.name);
+    if task.priority != Priority::None {
+        line.push_str(&format!(" ({})", task.priority));
+    }
+    if let Some(due) = task.due_date {
+        line.push_str(&format!(" due:{}", due));
+    }
+    for tag in &task.tags {
+        line.push_str(&format!(" {}", tag));
+    }
+    line
+}
+
+fn print_tree(tasks: &[TaskWithKids], depth: usize) {
+    for node in tasks {
+        println!("{:>4} {}{}", node.task.id, "  ".repeat(depth), format_task_line(&node.task));
+        print_tree(&node.subtasks, depth + 1);
+    }
+}
+
+fn print_flat(tasks: &[TaskWithKids], filter: &impl Fn(&Task) -> bool) {
+    for node in tasks {
+        if filter(&node.task) {
+            let parent = node.task.parent_id.map(|p| format!(" parent:{}", p)).unwrap_or_default();
+            println!("{:>4} {}{}", node.task.id, format_task_line(&node.task), parent);
+        }
+        print_flat(&node.subtasks, filter);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(input: &[&str]) -> Vec<String> {
+        input.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_add() {
+        let command = Command::parse(&args(&[
+            "add", "Write report", "--parent", "12", "--desc=Quarterly", "--priority", "high", "--tags", "@work,q3",
+        ]));
+        assert_eq!(command, Ok(Command::Add {
+            name: String::from("Write report"),
+            parent_id: Some(12),
+            description: Some(String::from("Quarterly")),
+            due_date: None,
+            priority: Priority::High,
+            tags: vec![String::from("@work"), String::from("q3")],
+            recurrence: None,
+        }));
+    }
+
+    #[test]
+    fn parse_list_and_edit() {
+        assert_eq!(
+            Command::parse(&args(&["list", "--completed", "--tree"])),
+            Ok(Command::List { completed: Some(true), tree: true, filter: None })
+        );
+        assert_eq!(
+            Command::parse(&args(&["list", "--filter", "tag:work due<7d"])),
+            Ok(Command::List { completed: None, tree: false, filter: Some(Query::parse("tag:work due<7d").unwrap()) })
+        );
+        assert!(Command::parse(&args(&["list", "--filter", "status:maybe"])).is_err());
+        assert_eq!(
+            Command::parse(&args(&["edit", "3", "--due", "-", "--parent", "root", "--desc", "-"])),
+            Ok(Command::Edit {
+                id: 3,
+                name: None,
+                description: Some(None),
+                due_date: Some(None),
+                priority: None,
+                recurrence: None,
+                parent_id: Some(None),
+            })
+        );
+        assert!(Command::parse(&args(&["edit", "3"])).is_err());
+        assert!(Command::parse(&args(&["edit", "3", "--name", " "])).is_err());
+        assert!(Command::parse(&args(&["add", ""])).is_err());
+    }
+
+    #[test]
+    fn parse_archive_and_history() {
+        assert_eq!(Command::parse(&args(&["archive", "4"])), Ok(Command::Archive { id: Some(4) }));
+        assert_eq!(Command::parse(&args(&["archive", "--all"])), Ok(Command::Archive { id: None }));
+        assert!(Command::parse(&args(&["archive", "4", "--all"])).is_err());
+        assert!(Command::parse(&args(&["archive"])).is_err());
+        assert_eq!(Command::parse(&args(&["unarchive", "4"])), Ok(Command::Unarchive { id: 4 }));
+        assert_eq!(Command::parse(&args(&["history", "4"])), Ok(Command::History { id: 4 }));
+    }
+
+    #[test]
+    fn parse_undo_and_redo() {
+        assert_eq!(Command::parse(&args(&["undo"])), Ok(Command::Undo));
+        assert_eq!(Command::parse(&args(&["redo"])), Ok(Command::Redo));
+        assert!(Command::parse(&args(&["undo", "3"])).is_err());
+    }
+
+    #[test]
+    fn parse_export_and_import() {
+        assert_eq!(
+            Command::parse(&args(&["export", "--output", "backup.json"])),
+            Ok(Command::Export { format: None, output: Some(String::from("backup.json")) })
+        );
+        assert_eq!(
+            Command::parse(&args(&["import", "-", "--format", "json", "--replace"])),
+            Ok(Command::Import { path: String::from("-"), format: Some(Format::Json), replace: true, columns: None })
+        );
+        assert_eq!(
+            Command::parse(&args(&["import", "items.csv", "--columns", "name=Title"])),
+            Ok(Command::Import {
+                path: String::from("items.csv"), format: None, replace: false,
+                columns: Some(ColumnMapping::default().map(csv::Field::Name, "Title")),
+            })
+        );
+        assert!(Command::parse(&args(&["import", "items.csv", "--columns", "title"])).is_err());
+        assert!(Command::parse(&args(&["export", "--format", "xls"])).is_err());
+        assert_eq!(resolve_format(None, Some("tasks.JSON")), Format::Json);
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert!(Command::parse(&args(&["add"])).is_err());
+        assert!(Command::parse(&args(&["done", "abc"])).is_err());
+        assert!(Command::parse(&args(&["rm", "1", "--force"])).is_err());
+        assert!(Command::parse(&args(&["add", "x", "--parent"])).is_err());
+        assert!(Command::parse(&args(&["list", "--open", "--completed"])).is_err());
+        assert!(Command::parse(&args(&["frobnicate"])).is_err());
+    }
+}
@@ -3,11 +3,20 @@ use std::{env, error::Error};
 
 pub struct Config {
     pub db_path: String,
+    /// Days a deleted task stays in the trash before it's purged, 0 keeps it forever
+    pub trash_retention_days: u32,
 }
 
 impl Config {
+    pub const DEFAULT_TRASH_RETENTION_DAYS: u32 = 30;
+
     pub fn build() -> Result<Self, Box<dyn Error>> {
         let db_path = env::var("DB_PATH")?.parse()?;
-        Ok(Self { db_path })
+        let trash_retention_days = match env::var("TRASH_RETENTION_DAYS") {
+            Ok(days) => days.trim().parse()
+                .map_err(|_| format!("Invalid TRASH_RETENTION_DAYS '{}', expected a number of days", days))?,
+            Err(_) => Self::DEFAULT_TRASH_RETENTION_DAYS,
+        };
+        Ok(Self { db_path, trash_retention_days })
     }    
 }
\ No newline at end of file
@@ -1,10 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
+use std::fs;
+use std::path::Path;
 
-use rusqlite::{Connection, Params};
+use chrono::{Local, NaiveDateTime};
+use rusqlite::{Connection, OptionalExtension, Params, params_from_iter, types::{Value, ValueRef}};
+use uuid::Uuid;
 
 use crate::task::*;
 use crate::config::Config;
+use crate::history::{self, HistoryStep, TaskChange, TaskSnapshot};
+use crate::migration;
+use crate::query::Query;
 
 pub struct DB {
     connection: Connection,
@@ -13,28 +20,37 @@ pub struct DB {
 impl DB {
 
     pub fn new(config: &Config) -> Result<Self, Box<dyn Error>> {
-        let connection = Connection::open(&config.db_path)?;
+        if let Some(dir) = Path::new(&config.db_path).parent()
+            && !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut connection = Connection::open(&config.db_path)?;
         connection.execute("PRAGMA foreign_keys = ON", ())?;
-        connection.execute(
-        "CREATE TABLE IF NOT EXISTS Task (
-                id            INTEGER PRIMARY KEY AUTOINCREMENT,
-                parent_id     INTEGER DEFAULT NULL,
-                name          TEXT NOT NULL,
-                completed     INTEGER DEFAULT 0,
-                description   TEXT,
-                creation_date TEXT DEFAULT CURRENT_TIMESTAMP,
-                
-                FOREIGN KEY (parent_id) REFERENCES Task(id) ON DELETE CASCADE
-            );",
-        ()
-        )?;
+        migration::run(&mut connection)?;
 
+        let db = Self { connection };
+        if config.trash_retention_days > 0 {
+            db.purge_trash(Some(config.trash_retention_days))?;
+        }
+        Ok(db)
+    }
 
-        Ok (Self {connection})
+    /// Runs `f` atomically. Calls made while a transaction is already open join it.
+    pub fn transaction<T>(&self, f: impl FnOnce() -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+        if !self.connection.is_autocommit() {
+            return f();
+        }
+        let tx = self.connection.unchecked_transaction()?;
+        let result = f()?;
+        tx.commit()?;
+        Ok(result)
     }
 
+    /// The task tree. With `completed` set, only tasks in that state are kept,
+    /// along with their ancestors so no subtask loses its place in the tree.
     pub fn select_tasks_hierarchy(&self, completed: Option<bool>) -> Result<Vec<TaskWithKids>, Box<dyn Error>> {
-        let all_tasks = self.select_tasks(completed)?;
+        let all_tasks = self.select_tasks(None)?;
 
         let mut by_parent: HashMap<Option<u32>, Vec<Task>> = HashMap::new();
 
@@ -42,6 +58,10 @@ impl DB {
             by_parent.entry(task.parent_id).or_default().push(task);
         }
 
+        for siblings in by_parent.values_mut() {
+            siblings.sort_by_key(Task::sibling_order);
+        }
+
         let root_tasks = by_parent.remove(&None).unwrap_or_default();
 
         let mut task_with_kids: Vec<TaskWithKids> = Vec::new();
@@ -49,59 +69,233 @@ impl DB {
             task_with_kids.push(TaskWithKids::get_recursive(task, &by_parent));
         }
 
-        Ok(task_with_kids)
+        match completed {
+            Some(completed) => Ok(TaskWithKids::filter(task_with_kids, &|task| task.completed == completed)),
+            None => Ok(task_with_kids),
+        }
     }
 
     pub fn select_completed_tasks(&self, completed: bool) -> Result<Vec<Task>, Box<dyn Error>> {
         self.query_to_tasks(
-            "SELECT * FROM Task WHERE completed = ?1", 
+            "SELECT * FROM Task WHERE completed = ?1 AND deleted_at IS NULL AND archived_at IS NULL", 
             [if completed { 1 } else { 0 }] )
     }
 
 
+    /// The tasks that are neither in the trash nor archived.
     pub fn select_tasks(&self, completed: Option<bool>) -> Result<Vec<Task>, Box<dyn Error>> {
         match completed {
             Some(compl) => self.query_to_tasks(
-                "SELECT * FROM Task WHERE completed = ?1", 
+                "SELECT * FROM Task WHERE completed = ?1 AND deleted_at IS NULL AND archived_at IS NULL", 
                 [if compl { 1 } else { 0 }]),
-            None => self.query_to_tasks("SELECT * FROM Task", [])
+            None => self.query_to_tasks("SELECT * FROM Task WHERE deleted_at IS NULL AND archived_at IS NULL", [])
         }
     }
 
+    pub fn select_trashed_tasks(&self) -> Result<Vec<Task>, Box<dyn Error>> {
+        self.query_to_tasks("SELECT * FROM Task WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC, id", [])
+    }
+
+    pub fn select_archived_tasks(&self) -> Result<Vec<Task>, Box<dyn Error>> {
+        self.query_to_tasks(
+            "SELECT * FROM Task WHERE archived_at IS NOT NULL AND deleted_at IS NULL ORDER BY archived_at DESC, id", [])
+    }
+
+    /// Also finds trashed tasks, so an import can bring them back.
+    pub fn select_task_by_uuid(&self, uuid: &str) -> Result<Option<Task>, Box<dyn Error>> {
+        Ok(self.query_to_tasks("SELECT * FROM Task WHERE uuid = ?1", [uuid])?.pop())
+    }
+
+    /// The tasks matching a filter query, without their ancestors.
+    pub fn select_tasks_matching(&self, query: &Query) -> Result<Vec<Task>, Box<dyn Error>> {
+        let (sql, params) = query.to_sql(Local::now().naive_local());
+        self.query_to_tasks(&sql, params_from_iter(params))
+    }
+
+    /// Full-text search, best matches first. `query` uses the FTS5 query syntax.
+    pub fn search_tasks(&self, query: &str) -> Result<Vec<SearchHit>, Box<dyn Error>> {
+        let start = SearchHit::MATCH_START.to_string();
+        let end = SearchHit::MATCH_END.to_string();
+        let mut stmt = self.connection.prepare(
+            "SELECT TaskSearch.rowid,
+                    highlight(TaskSearch, 0, ?2, ?3),
+                    snippet(TaskSearch, -1, ?2, ?3, '…', 12),
+                    bm25(TaskSearch, 10.0, 1.0) AS rank
+             FROM TaskSearch JOIN Task ON Task.id = TaskSearch.rowid
+             WHERE TaskSearch MATCH ?1 AND Task.deleted_at IS NULL AND Task.archived_at IS NULL
+             ORDER BY rank")?;
+        let hits = stmt.query_map((query, &start, &end), |row| Ok(SearchHit {
+            task_id: row.get(0)?,
+            name: row.get(1)?,
+            snippet: row.get(2)?,
+            rank: row.get(3)?,
+        }))?;
+
+        let mut result = Vec::new();
+        for hit in hits {
+            result.push(hit?);
+        }
+        Ok(result)
+    }
+
     pub fn select_task_subtasks(&self, parent_id: u32) -> Result<Vec<Task>, Box<dyn Error>> {
-        self.query_to_tasks("SELECT * FROM Task WHERE parent_id = ?1", [parent_id])
+        self.query_to_tasks(
+            "SELECT * FROM Task WHERE parent_id = ?1 AND deleted_at IS NULL AND archived_at IS NULL
+             ORDER BY priority DESC, position",
+            [parent_id])
     }
 
     fn query_to_tasks<P: Params> (&self, query: &str, params: P) -> Result<Vec<Task>, Box<dyn Error>>  {
         let mut stmt = self.connection.prepare(query)?;
         let task_iter = stmt.query_map(params, |row| {
-            Ok(SelectTask::from_row(row)?)
+            SelectTask::from_row(row)
         })?;
 
+        let mut tags = self.select_tags_by_task(None)?;
         let mut result = Vec::new();
         for task in task_iter {
-            result.push(Task::from_select(task?)?);
+            let mut task = Task::from_select(task?)?;
+            task.tags = tags.remove(&task.id).unwrap_or_default();
+            result.push(task);
         }
 
         Ok(result)
         
     }
 
+    /// The tags of every task, or only of the tasks in `task_ids`.
+    fn select_tags_by_task(&self, task_ids: Option<&HashSet<u32>>) -> Result<HashMap<u32, Vec<String>>, Box<dyn Error>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT TaskTag.task_id, Tag.name FROM TaskTag
+             JOIN Tag ON Tag.id = TaskTag.tag_id
+             WHERE ?1 IS NULL OR TaskTag.task_id IN (SELECT value FROM json_each(?1))
+             ORDER BY Tag.name COLLATE NOCASE")?;
+        let task_ids = task_ids.map(id_list).transpose()?;
+        let rows = stmt.query_map((task_ids,), |row| Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut by_task: HashMap<u32, Vec<String>> = HashMap::new();
+        for row in rows {
+            let (task_id, name) = row?;
+            by_task.entry(task_id).or_default().push(name);
+        }
+        Ok(by_task)
+    }
+
+    pub fn select_tags(&self) -> Result<Vec<Tag>, Box<dyn Error>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT Tag.id, Tag.name, COUNT(Task.id) FROM Tag
+             LEFT JOIN TaskTag ON TaskTag.tag_id = Tag.id
+             LEFT JOIN Task ON Task.id = TaskTag.task_id AND Task.deleted_at IS NULL AND Task.archived_at IS NULL
+             GROUP BY Tag.id
+             ORDER BY Tag.name COLLATE NOCASE")?;
+        let tags = stmt.query_map([], |row| Ok(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            task_count: row.get(2)?,
+        }))?;
+
+        let mut result = Vec::new();
+        for tag in tags {
+            result.push(tag?);
+        }
+        Ok(result)
+    }
+
+    pub fn create_tag(&self, name: &str) -> Result<u32, Box<dyn Error>> {
+        self.connection.execute("INSERT OR IGNORE INTO Tag (name) VALUES (?1)", (name,))?;
+        let id = self.connection.query_row(
+            "SELECT id FROM Tag WHERE name = ?1", (name,), |row| row.get(0))?;
+        Ok(id)
+    }
+
+    pub fn rename_tag(&self, old_name: &str, new_name: &str) -> Result<(), Box<dyn Error>> {
+        let changed = self.connection.execute(
+            "UPDATE Tag SET name = ?2 WHERE name = ?1", (old_name, new_name))?;
+        if changed == 0 {
+            return Err(format!("Tag '{}' not found", old_name).into());
+        }
+        Ok(())
+    }
+
+    pub fn delete_tag(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.connection.execute("DELETE FROM Tag WHERE name = ?1", (name,))?;
+        Ok(())
+    }
+
+    pub fn add_task_tag(&self, task_id: u32, name: &str) -> Result<(), Box<dyn Error>> {
+        let tag_id = self.create_tag(name)?;
+        self.connection.execute(
+            "INSERT OR IGNORE INTO TaskTag (task_id, tag_id) VALUES (?1, ?2)", (task_id, tag_id))?;
+        Ok(())
+    }
+
+    pub fn remove_task_tag(&self, task_id: u32, name: &str) -> Result<(), Box<dyn Error>> {
+        self.connection.execute(
+            "DELETE FROM TaskTag WHERE task_id = ?1 AND tag_id = (SELECT id FROM Tag WHERE name = ?2)",
+            (task_id, name))?;
+        Ok(())
+    }
+
+    pub fn set_task_tags(&self, task_id: u32, names: &[String]) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            self.connection.execute("DELETE FROM TaskTag WHERE task_id = ?1", (task_id,))?;
+            for name in names {
+                self.add_task_tag(task_id, name)?;
+            }
+            Ok(())
+        })
+    }
+
     pub fn create_task(&self, task: &CreateTask) -> Result<i32, Box<dyn Error>>{
+        self.transaction(|| self.insert_task(task))
+    }
+
+    fn insert_task(&self, task: &CreateTask) -> Result<i32, Box<dyn Error>> {
         self.connection.execute(
-            "INSERT INTO Task (parent_id, name, description) VALUES (?1, ?2, ?3)",
+            "INSERT INTO Task (parent_id, name, description, due_date, priority, recurrence, position,
+                id, completed, creation_date, uuid, completed_at, collapsed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6,
+                (SELECT COALESCE(MAX(position), 0) + 1 FROM Task WHERE parent_id IS ?1),
+                ?7, ?8, COALESCE(?9, CURRENT_TIMESTAMP), ?10, ?11, ?12)",
         (
             &task.parent_id,
             &task.name,
-            &task.description
+            &task.description,
+            task.due_date.as_ref().map(DueDate::to_db_string),
+            task.priority.to_db(),
+            task.recurrence.as_ref().map(Recurrence::to_rrule),
+            &task.id,
+            task.completed,
+            task.creation_date.map(|date| date.format(DB_DATE_FORMAT).to_string()),
+            task.uuid.clone().unwrap_or_else(|| Uuid::new_v4().to_string()),
+            task.completed_at.filter(|_| task.completed).map(|date| date.format(DB_DATE_FORMAT).to_string()),
+            task.collapsed,
         ))?;
 
         let insert_id = self.connection.last_insert_rowid() as i32;
+        self.insert_task_event(insert_id as u32, TaskEventKind::Create, None, None, None)?;
 
         Ok(insert_id)
     }
 
+    /// Updates the task and records what changed in its history.
     pub fn update_task(&self, task: &UpdateTask) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            let old = self.query_to_tasks("SELECT * FROM Task WHERE id = ?1", (task.id,))?.pop();
+            self.write_task_update(task)?;
+            match old {
+                Some(old) => self.insert_update_events(&old, task),
+                None => Ok(()),
+            }
+        })
+    }
+
+    fn write_task_update(&self, task: &UpdateTask) -> Result<(), Box<dyn Error>> {
+        let due_date = task.due_date.map(|due| due.as_ref().map(DueDate::to_db_string));
+        let priority = task.priority.map(Priority::to_db);
+        let recurrence = task.recurrence.as_ref().map(|rule| rule.as_ref().map(Recurrence::to_rrule));
+        let completed_at = task.completed_at.map(|date| date.format(DB_DATE_FORMAT).to_string());
+
         let mut params = Vec::new();
         let mut query = "UPDATE Task SET ".to_string();
         if let Some(ref name) = task.name {
@@ -113,16 +307,40 @@ impl DB {
             params.push(description as &dyn rusqlite::ToSql);
         }
         if let Some(ref parent_id) = task.parent_id {
-            query.push_str("parent_id = ?, ");
+            // A moved task goes after its new siblings
+            query.push_str("parent_id = ?, \
+                position = (SELECT COALESCE(MAX(position), 0) + 1 FROM Task WHERE parent_id IS ?), ");
+            params.push(parent_id as &dyn rusqlite::ToSql);
             params.push(parent_id as &dyn rusqlite::ToSql);
         }
         if let Some(ref completed) = task.completed {
-            query.push_str("completed = ?, ");
+            // Completing an already completed task keeps its completion time
+            query.push_str("completed = ?, completed_at = CASE WHEN ? = 0 THEN NULL WHEN ? IS NOT NULL THEN ? \
+                WHEN completed = 1 THEN completed_at ELSE datetime('now') END, ");
+            params.push(completed as &dyn rusqlite::ToSql);
             params.push(completed as &dyn rusqlite::ToSql);
+            params.push(&completed_at as &dyn rusqlite::ToSql);
+            params.push(&completed_at as &dyn rusqlite::ToSql);
+        }
+        if let Some(ref due_date) = due_date {
+            query.push_str("due_date = ?, ");
+            params.push(due_date as &dyn rusqlite::ToSql);
+        }
+        if let Some(ref priority) = priority {
+            query.push_str("priority = ?, ");
+            params.push(priority as &dyn rusqlite::ToSql);
+        }
+        if let Some(ref recurrence) = recurrence {
+            query.push_str("recurrence = ?, ");
+            params.push(recurrence as &dyn rusqlite::ToSql);
+        }
+        if let Some(ref collapsed) = task.collapsed {
+            query.push_str("collapsed = ?, ");
+            params.push(collapsed as &dyn rusqlite::ToSql);
         }
         query.pop(); query.pop();
 
-        query.push_str("WHERE id = ?");
+        query.push_str(" WHERE id = ?");
 
         params.push(&task.id as &dyn rusqlite::ToSql);
 
@@ -132,16 +350,482 @@ impl DB {
         Ok(())
     }
 
-    pub fn delete_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+    /// Records one event per field `update` changes on `old`. Positions and
+    /// folding aren't recorded.
+    fn insert_update_events(&self, old: &Task, update: &UpdateTask) -> Result<(), Box<dyn Error>> {
+        let mut changes: Vec<(&str, Option<String>, Option<String>)> = Vec::new();
+        if let Some(name) = &update.name {
+            changes.push(("name", Some(old.name.clone()), Some(name.clone())));
+        }
+        if let Some(description) = &update.description {
+            changes.push(("description", old.description.clone(), description.clone()));
+        }
+        if let Some(parent_id) = update.parent_id {
+            changes.push(("parent_id", old.parent_id.map(|id| id.to_string()), parent_id.map(|id| id.to_string())));
+        }
+        if let Some(due_date) = update.due_date {
+            changes.push(("due_date", old.due_date.map(|due| due.to_string()), due_date.map(|due| due.to_string())));
+        }
+        if let Some(priority) = update.priority {
+            changes.push(("priority", Some(old.priority.to_string()), Some(priority.to_string())));
+        }
+        if let Some(recurrence) = &update.recurrence {
+            changes.push(("recurrence", old.recurrence.as_ref().map(Recurrence::to_string),
+                recurrence.as_ref().map(Recurrence::to_string)));
+        }
+
+        for (field, old_value, new_value) in changes {
+            if old_value != new_value {
+                self.insert_task_event(old.id, TaskEventKind::Update, Some(field), old_value, new_value)?;
+            }
+        }
+        if let Some(completed) = update.completed
+            && (completed != 0) != old.completed {
+            let kind = if completed != 0 { TaskEventKind::Complete } else { TaskEventKind::Reopen };
+            self.insert_task_event(old.id, kind, None, None, None)?;
+        }
+        Ok(())
+    }
+
+    fn insert_task_event(
+        &self,
+        task_id: u32,
+        kind: TaskEventKind,
+        field: Option<&str>,
+        old_value: Option<String>,
+        new_value: Option<String>,
+    ) -> Result<(), Box<dyn Error>> {
         self.connection.execute(
-            "DELETE FROM Task WHERE id = ?1", 
-        (task_id,))?;
-        
+            "INSERT INTO TaskEvent (task_id, kind, field, old_value, new_value) VALUES (?1, ?2, ?3, ?4, ?5)",
+            (task_id, kind.to_db(), field, old_value, new_value))?;
+        Ok(())
+    }
+
+    /// The task's history, oldest first.
+    pub fn select_task_events(&self, task_id: u32) -> Result<Vec<TaskEvent>, Box<dyn Error>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT id, task_id, kind, field, old_value, new_value, created_at FROM TaskEvent
+             WHERE task_id = ?1 ORDER BY id")?;
+        let rows = stmt.query_map((task_id,), |row| Ok((
+            row.get::<_, u32>(0)?,
+            row.get::<_, u32>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, Option<String>>(3)?,
+            row.get::<_, Option<String>>(4)?,
+            row.get::<_, Option<String>>(5)?,
+            row.get::<_, String>(6)?,
+        )))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            let (id, task_id, kind, field, old_value, new_value, created_at) = row?;
+            result.push(TaskEvent {
+                id,
+                task_id,
+                kind: TaskEventKind::from_db(&kind)?,
+                field,
+                old_value,
+                new_value,
+                created_at: NaiveDateTime::parse_from_str(&created_at, DB_DATE_FORMAT)?,
+            });
+        }
+        Ok(result)
+    }
+
+    /// Exchanges the sibling positions of two tasks in one transaction.
+    pub fn swap_task_positions(&self, first_id: u32, second_id: u32) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            let position_of = |id: u32| -> Result<u32, rusqlite::Error> {
+                self.connection.query_row("SELECT position FROM Task WHERE id = ?1", (id,), |row| row.get(0))
+            };
+            let first = position_of(first_id)?;
+            let second = position_of(second_id)?;
+            self.connection.execute("UPDATE Task SET position = ?1 WHERE id = ?2", (second, first_id))?;
+            self.connection.execute("UPDATE Task SET position = ?1 WHERE id = ?2", (first, second_id))?;
+            Ok(())
+        })
+    }
+
+    /// Moves every task that is neither trashed nor archived to the trash, e.g.
+    /// before restoring a backup, like [`Self::delete_task`] does for one subtree.
+    pub fn trash_active_tasks(&self) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            self.connection.execute(
+                "INSERT INTO TaskEvent (task_id, kind)
+                 SELECT id, ?1 FROM Task WHERE deleted_at IS NULL AND archived_at IS NULL",
+                (TaskEventKind::Delete.to_db(),))?;
+            self.connection.execute(
+                "UPDATE Task SET deleted_at = datetime('now') WHERE deleted_at IS NULL AND archived_at IS NULL",
+                ())?;
+            Ok(())
+        })
+    }
+
+    /// The ids of every row, including trashed and archived tasks.
+    pub fn select_task_ids(&self) -> Result<HashSet<u32>, Box<dyn Error>> {
+        let mut stmt = self.connection.prepare("SELECT id FROM Task")?;
+        let ids = stmt.query_map([], |row| row.get(0))?.collect::<Result<HashSet<u32>, _>>()?;
+        Ok(ids)
+    }
+
+    /// Moves the task and its subtasks to the trash. Subtasks trashed before
+    /// keep their own deletion time.
+    pub fn delete_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            self.connection.execute(
+                "WITH RECURSIVE Subtree(id) AS (
+                     SELECT ?1
+                     UNION ALL
+                     SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+                 )
+                 INSERT INTO TaskEvent (task_id, kind)
+                 SELECT id, ?2 FROM Task WHERE id IN Subtree AND deleted_at IS NULL",
+                (task_id, TaskEventKind::Delete.to_db()))?;
+            self.connection.execute(
+                "WITH RECURSIVE Subtree(id) AS (
+                     SELECT ?1
+                     UNION ALL
+                     SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+                 )
+                 UPDATE Task SET deleted_at = datetime('now')
+                 WHERE id IN Subtree AND deleted_at IS NULL",
+                (task_id,))?;
+            Ok(())
+        })
+    }
+
+    /// Takes the task and the subtasks trashed along with it out of the trash.
+    /// Subtasks trashed on their own before stay there.
+    pub fn restore_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            self.connection.execute(
+                "WITH RECURSIVE Subtree(id) AS (
+                     SELECT ?1
+                     UNION ALL
+                     SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+                 )
+                 INSERT INTO TaskEvent (task_id, kind)
+                 SELECT id, ?2 FROM Task
+                 WHERE id IN Subtree AND deleted_at >= (SELECT deleted_at FROM Task WHERE id = ?1)",
+                (task_id, TaskEventKind::Restore.to_db()))?;
+            self.connection.execute(
+                "WITH RECURSIVE Subtree(id) AS (
+                     SELECT ?1
+                     UNION ALL
+                     SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+                 )
+                 UPDATE Task SET deleted_at = NULL
+                 WHERE id IN Subtree AND deleted_at >= (SELECT deleted_at FROM Task WHERE id = ?1)",
+                (task_id,))?;
+            Ok(())
+        })
+    }
+
+    /// Takes only this task out of the trash and the archive; its subtasks stay
+    /// where they are.
+    pub fn revive_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            self.connection.execute(
+                "INSERT INTO TaskEvent (task_id, kind) SELECT id, ?2 FROM Task WHERE id = ?1 AND deleted_at IS NOT NULL",
+                (task_id, TaskEventKind::Restore.to_db()))?;
+            self.connection.execute(
+                "INSERT INTO TaskEvent (task_id, kind) SELECT id, ?2 FROM Task WHERE id = ?1 AND archived_at IS NOT NULL",
+                (task_id, TaskEventKind::Unarchive.to_db()))?;
+            self.connection.execute(
+                "UPDATE Task SET deleted_at = NULL, archived_at = NULL WHERE id = ?1", (task_id,))?;
+            Ok(())
+        })
+    }
+
+    /// Moves the task and its subtasks to the archive, keeping their place in the tree.
+    pub fn archive_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            self.connection.execute(
+                "WITH RECURSIVE Subtree(id) AS (
+                     SELECT ?1
+                     UNION ALL
+                     SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+                 )
+                 INSERT INTO TaskEvent (task_id, kind)
+                 SELECT id, ?2 FROM Task WHERE id IN Subtree AND archived_at IS NULL",
+                (task_id, TaskEventKind::Archive.to_db()))?;
+            self.connection.execute(
+                "WITH RECURSIVE Subtree(id) AS (
+                     SELECT ?1
+                     UNION ALL
+                     SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+                 )
+                 UPDATE Task SET archived_at = datetime('now')
+                 WHERE id IN Subtree AND archived_at IS NULL",
+                (task_id,))?;
+            Ok(())
+        })
+    }
+
+    /// Takes the task and the subtasks archived along with it out of the archive.
+    /// Subtasks archived on their own before stay there.
+    pub fn unarchive_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            self.connection.execute(
+                "WITH RECURSIVE Subtree(id) AS (
+                     SELECT ?1
+                     UNION ALL
+                     SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+                 )
+                 INSERT INTO TaskEvent (task_id, kind)
+                 SELECT id, ?2 FROM Task
+                 WHERE id IN Subtree AND archived_at >= (SELECT archived_at FROM Task WHERE id = ?1)",
+                (task_id, TaskEventKind::Unarchive.to_db()))?;
+            self.connection.execute(
+                "WITH RECURSIVE Subtree(id) AS (
+                     SELECT ?1
+                     UNION ALL
+                     SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+                 )
+                 UPDATE Task SET archived_at = NULL
+                 WHERE id IN Subtree AND archived_at >= (SELECT archived_at FROM Task WHERE id = ?1)",
+                (task_id,))?;
+            Ok(())
+        })
+    }
+
+    /// Deletes the task and its subtasks for good, also from the undo history.
+    pub fn purge_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            self.purge_subtrees("SELECT id FROM Task WHERE id = ?1", (task_id,))?;
+            Ok(())
+        })
+    }
+
+    /// Deletes the tasks that have been in the trash for more than `older_than_days`,
+    /// or all of them. Returns how many rows were removed.
+    pub fn purge_trash(&self, older_than_days: Option<u32>) -> Result<usize, Box<dyn Error>> {
+        self.transaction(|| {
+            self.purge_subtrees(
+                "SELECT id FROM Task WHERE deleted_at IS NOT NULL
+                    AND (?1 IS NULL OR deleted_at < datetime('now', '-' || ?1 || ' days'))",
+                (older_than_days,))
+        })
+    }
+
+    /// Deletes the rows `roots` selects with their subtrees and forgets them in
+    /// the undo history. Returns how many rows were removed.
+    fn purge_subtrees<P: Params>(&self, roots: &str, params: P) -> Result<usize, Box<dyn Error>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "WITH RECURSIVE Subtree(id) AS (
+                 {}
+                 UNION
+                 SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+             )
+             SELECT id FROM Subtree", roots))?;
+        let ids = stmt.query_map(params, |row| row.get(0))?.collect::<Result<HashSet<u32>, _>>()?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        self.forget_history(&ids)?;
+        let mut delete = self.connection.prepare("DELETE FROM Task WHERE id = ?1")?;
+        for id in &ids {
+            delete.execute((id,))?;
+        }
+        Ok(ids.len())
+    }
+
+    /// Rewrites the undo history without the given tasks.
+    fn forget_history(&self, ids: &HashSet<u32>) -> Result<(), Box<dyn Error>> {
+        let mut stmt = self.connection.prepare("SELECT id, changes FROM UndoHistory")?;
+        let steps = stmt.query_map([], |row| Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for (id, changes) in steps {
+            let changes: Vec<TaskChange> = serde_json::from_str(&changes)?;
+            let count = changes.len();
+            match history::forget(changes, ids) {
+                Some(kept) if kept.len() == count => {}
+                Some(kept) => {
+                    self.connection.execute(
+                        "UPDATE UndoHistory SET changes = ?1 WHERE id = ?2", (serde_json::to_string(&kept)?, id))?;
+                }
+                None => {
+                    self.connection.execute("DELETE FROM UndoHistory WHERE id = ?1", (id,))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The ids of the given tasks and all their descendants.
+    pub fn select_subtree_ids(&self, roots: &[u32]) -> Result<HashSet<u32>, Box<dyn Error>> {
+        let mut stmt = self.connection.prepare(
+            "WITH RECURSIVE Subtree(id) AS (
+                 SELECT Task.id FROM Task WHERE Task.id IN (SELECT value FROM json_each(?1))
+                 UNION
+                 SELECT Task.id FROM Task JOIN Subtree ON Task.parent_id = Subtree.id
+             )
+             SELECT id FROM Subtree")?;
+        let ids = stmt.query_map((serde_json::to_string(roots)?,), |row| row.get(0))?
+            .collect::<Result<HashSet<u32>, _>>()?;
+        Ok(ids)
+    }
+
+    /// The ids of the tasks created after `id`, which row ids only ever grow past.
+    pub fn select_task_ids_after(&self, id: u32) -> Result<HashSet<u32>, Box<dyn Error>> {
+        let mut stmt = self.connection.prepare("SELECT id FROM Task WHERE id > ?1")?;
+        let ids = stmt.query_map((id,), |row| row.get(0))?.collect::<Result<HashSet<u32>, _>>()?;
+        Ok(ids)
+    }
+
+    pub fn select_max_task_id(&self) -> Result<u32, Box<dyn Error>> {
+        Ok(self.connection.query_row("SELECT COALESCE(MAX(id), 0) FROM Task", [], |row| row.get(0))?)
+    }
+
+    /// Task rows with their tags for the undo history, all of them or only the
+    /// ones in `task_ids`. `collapsed` is left out because folding a task isn't
+    /// an undoable change.
+    pub fn select_snapshots(&self, task_ids: Option<&HashSet<u32>>) -> Result<Vec<TaskSnapshot>, Box<dyn Error>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT * FROM Task WHERE ?1 IS NULL OR id IN (SELECT value FROM json_each(?1)) ORDER BY id")?;
+        let names: Vec<String> = stmt.column_names().into_iter().map(str::to_string).collect();
+        let mut tags = self.select_tags_by_task(task_ids)?;
+
+        let mut rows = stmt.query((task_ids.map(id_list).transpose()?,))?;
+        let mut result = Vec::new();
+        while let Some(row) = rows.next()? {
+            let mut snapshot = TaskSnapshot {
+                id: row.get("id")?,
+                parent_id: row.get("parent_id")?,
+                columns: serde_json::Map::new(),
+                tags: Vec::new(),
+            };
+            for (index, name) in names.iter().enumerate() {
+                if ["id", "parent_id", "collapsed"].contains(&name.as_str()) {
+                    continue;
+                }
+                let value = match row.get_ref(index)? {
+                    ValueRef::Null => serde_json::Value::Null,
+                    ValueRef::Integer(value) => serde_json::Value::from(value),
+                    ValueRef::Real(value) => serde_json::Value::from(value),
+                    ValueRef::Text(text) => serde_json::Value::from(String::from_utf8_lossy(text)),
+                    ValueRef::Blob(_) => return Err(format!("Unexpected blob in Task.{}", name).into()),
+                };
+                snapshot.columns.insert(name.clone(), value);
+            }
+            snapshot.tags = tags.remove(&snapshot.id).unwrap_or_default();
+            result.push(snapshot);
+        }
+        Ok(result)
+    }
+
+    /// Brings every task in `changes` to its `after` state: rows are written
+    /// back parents first, then the tasks that shouldn't exist are deleted.
+    pub fn restore_snapshots(&self, changes: &[TaskChange]) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            let mut pending: Vec<&TaskSnapshot> = changes.iter().filter_map(|c| c.after.as_ref()).collect();
+            while !pending.is_empty() {
+                let ids: HashSet<u32> = pending.iter().map(|task| task.id).collect();
+                let (ready, waiting): (Vec<&TaskSnapshot>, Vec<&TaskSnapshot>) = pending.into_iter()
+                    .partition(|task| task.parent_id.is_none_or(|parent| parent == task.id || !ids.contains(&parent)));
+                if ready.is_empty() {
+                    return Err("Can't restore tasks whose parents form a cycle".into());
+                }
+                for task in ready {
+                    self.write_snapshot(task)?;
+                }
+                pending = waiting;
+            }
+
+            for change in changes.iter().filter(|c| c.after.is_none()) {
+                if let Some(task) = &change.before {
+                    self.connection.execute("DELETE FROM Task WHERE id = ?1", (task.id,))?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Notes an undo or redo in the history of the tasks the step leaves in place.
+    pub fn insert_step_events(&self, changes: &[TaskChange], kind: TaskEventKind, label: &str) -> Result<(), Box<dyn Error>> {
+        for task in changes.iter().filter_map(|change| change.after.as_ref()) {
+            self.insert_task_event(task.id, kind, None, None, Some(label.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Updates the task's row to `task`, or inserts it if it doesn't exist.
+    fn write_snapshot(&self, task: &TaskSnapshot) -> Result<(), Box<dyn Error>> {
+        let mut names = vec![String::from("parent_id")];
+        let mut values = vec![task.parent_id.map(|id| Value::Integer(id as i64)).unwrap_or(Value::Null)];
+        for (name, value) in &task.columns {
+            names.push(format!("\"{}\"", name.replace('"', "\"\"")));
+            values.push(match value {
+                serde_json::Value::Null => Value::Null,
+                serde_json::Value::Bool(value) => Value::Integer(*value as i64),
+                serde_json::Value::Number(number) => match number.as_i64() {
+                    Some(value) => Value::Integer(value),
+                    None => Value::Real(number.as_f64().unwrap_or_default()),
+                },
+                serde_json::Value::String(text) => Value::Text(text.clone()),
+                other => return Err(format!("Invalid value {} for Task.{}", other, name).into()),
+            });
+        }
+        values.push(Value::Integer(task.id as i64));
+
+        let exists: bool = self.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM Task WHERE id = ?1)", (task.id,), |row| row.get(0))?;
+        let query = if exists {
+            let assignments: Vec<String> = names.iter().map(|name| format!("{} = ?", name)).collect();
+            format!("UPDATE Task SET {} WHERE id = ?", assignments.join(", "))
+        } else {
+            format!("INSERT INTO Task ({}, id) VALUES ({})", names.join(", "), vec!["?"; values.len()].join(", "))
+        };
+        self.connection.execute(&query, params_from_iter(values))?;
+        self.set_task_tags(task.id, &task.tags)
+    }
+
+    /// Records an undoable step. The steps that were undone can't be redone
+    /// after a new change, and only the latest `limit` steps are kept.
+    pub fn insert_history_step(&self, label: &str, changes: &[TaskChange], limit: u32) -> Result<(), Box<dyn Error>> {
+        self.transaction(|| {
+            self.connection.execute("DELETE FROM UndoHistory WHERE undone = 1", ())?;
+            self.connection.execute(
+                "INSERT INTO UndoHistory (label, changes) VALUES (?1, ?2)",
+                (label, serde_json::to_string(changes)?))?;
+            self.connection.execute(
+                "DELETE FROM UndoHistory WHERE id NOT IN (SELECT id FROM UndoHistory ORDER BY id DESC LIMIT ?1)",
+                (limit,))?;
+            Ok(())
+        })
+    }
+
+    /// The step to undo next, or with `undone` set the step to redo next.
+    pub fn select_history_step(&self, undone: bool) -> Result<Option<HistoryStep>, Box<dyn Error>> {
+        let query = if undone {
+            "SELECT id, label, changes FROM UndoHistory WHERE undone = 1 ORDER BY id LIMIT 1"
+        } else {
+            "SELECT id, label, changes FROM UndoHistory WHERE undone = 0 ORDER BY id DESC LIMIT 1"
+        };
+        let row = self.connection.query_row(query, [], |row| {
+            Ok((row.get::<_, u32>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        }).optional()?;
+
+        match row {
+            Some((id, label, changes)) => Ok(Some(HistoryStep { id, label, changes: serde_json::from_str(&changes)? })),
+            None => Ok(None),
+        }
+    }
+
+    pub fn set_history_step_undone(&self, id: u32, undone: bool) -> Result<(), Box<dyn Error>> {
+        self.connection.execute("UPDATE UndoHistory SET undone = ?1 WHERE id = ?2", (undone, id))?;
         Ok(())
     }
 } 
 
 
+/// A JSON array of ids, for `json_each` in queries.
+fn id_list(ids: &HashSet<u32>) -> Result<String, serde_json::Error> {
+    serde_json::to_string(ids)
+}
+
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -188,7 +872,11 @@ mod tests {
         let create_task = CreateTask{ 
             name: String::from("Cleaning"), 
             parent_id: None, 
-            description: None 
+            description: None,
+            due_date: None,
+            priority: Priority::None,
+            recurrence: None,
+            ..Default::default()
         };
         db.create_task(&create_task)?;
 
@@ -219,8 +907,13 @@ mod tests {
             id: 9,
             name: None,
             parent_id: None,
-            description: Some(String::from("New description")),
-            completed: Some(1)
+            description: Some(Some(String::from("New description"))),
+            completed: Some(1),
+            completed_at: None,
+            due_date: None,
+            priority: None,
+            recurrence: None,
+            collapsed: None,
         };
 
         db.update_task(&update_task)?;
@@ -239,4 +932,250 @@ mod tests {
         Ok(())
     }
 
+    fn memory_db() -> Result<DB, Box<dyn Error>> {
+        DB::new(&Config { db_path: String::from(":memory:"), trash_retention_days: 0 })
+    }
+
+    #[test]
+    fn due_date_round_trip() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let due = DueDate::parse("2030-01-15 09:30")?;
+        let id = db.create_task(&CreateTask {
+            name: String::from("Report"),
+            due_date: Some(due),
+            ..Default::default()
+        })? as u32;
+
+        assert_eq!(db.select_tasks(None)?[0].due_date, Some(due));
+
+        db.update_task(&UpdateTask { id, due_date: Some(None), ..Default::default() })?;
+        assert_eq!(db.select_tasks(None)?[0].due_date, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn task_tags() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let id = db.create_task(&CreateTask { name: String::from("Deploy"), ..Default::default() })? as u32;
+
+        db.set_task_tags(id, &[String::from("@work"), String::from("#urgent")])?;
+        assert_eq!(db.select_tasks(None)?[0].tags, ["#urgent", "@work"]);
+
+        db.add_task_tag(id, "@WORK")?;
+        db.remove_task_tag(id, "#urgent")?;
+        assert_eq!(db.select_tasks(None)?[0].tags, ["@work"]);
+
+        db.rename_tag("@work", "@office")?;
+        assert_eq!(db.select_tasks(None)?[0].tags, ["@office"]);
+
+        let tags = db.select_tags()?;
+        assert_eq!(tags.len(), 2);
+        assert_eq!(tags.iter().find(|t| t.name == "@office").map(|t| t.task_count), Some(1));
+
+        db.delete_tag("@office")?;
+        assert!(db.select_tasks(None)?[0].tags.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn new_tasks_are_appended_to_their_siblings() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let parent = db.create_task(&CreateTask { name: String::from("parent"), ..Default::default() })? as u32;
+        let first = db.create_task(&CreateTask { name: String::from("first"), parent_id: Some(parent), ..Default::default() })? as u32;
+        let second = db.create_task(&CreateTask { name: String::from("second"), parent_id: Some(parent), ..Default::default() })? as u32;
+
+        let names = |db: &DB| -> Result<Vec<String>, Box<dyn Error>> {
+            Ok(db.select_task_subtasks(parent)?.into_iter().map(|t| t.name).collect())
+        };
+        assert_eq!(names(&db)?, ["first", "second"]);
+
+        db.swap_task_positions(first, second)?;
+        assert_eq!(names(&db)?, ["second", "first"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn moving_task_to_root() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let parent = db.create_task(&CreateTask { name: String::from("parent"), ..Default::default() })? as u32;
+        let child = db.create_task(&CreateTask { name: String::from("child"), parent_id: Some(parent), ..Default::default() })? as u32;
+
+        db.update_task(&UpdateTask { id: child, parent_id: Some(None), ..Default::default() })?;
+
+        let tasks = db.select_tasks_hierarchy(None)?;
+        let names: Vec<&str> = tasks.iter().map(|t| t.task.name.as_str()).collect();
+        assert_eq!(names, ["parent", "child"]);
+        assert!(tasks[0].subtasks.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn completion_filter_keeps_parents() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let parent = db.create_task(&CreateTask { name: String::from("open parent"), ..Default::default() })? as u32;
+        db.create_task(&CreateTask {
+            name: String::from("done child"), parent_id: Some(parent), completed: true, ..Default::default()
+        })?;
+        db.create_task(&CreateTask { name: String::from("open child"), parent_id: Some(parent), ..Default::default() })?;
+
+        let done = db.select_tasks_hierarchy(Some(true))?;
+        assert_eq!(done.len(), 1);
+        let names: Vec<&str> = done[0].subtasks.iter().map(|t| t.task.name.as_str()).collect();
+        assert_eq!(names, ["done child"]);
+
+        let open = db.select_tasks_hierarchy(Some(false))?;
+        assert_eq!(open[0].subtasks.len(), 1);
+        assert_eq!(open[0].subtasks[0].task.name, "open child");
+        Ok(())
+    }
+
+    #[test]
+    fn trash_is_purged_after_retention() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let old = db.create_task(&CreateTask { name: String::from("old"), ..Default::default() })? as u32;
+        db.create_task(&CreateTask { name: String::from("old child"), parent_id: Some(old), ..Default::default() })?;
+        let recent = db.create_task(&CreateTask { name: String::from("recent"), ..Default::default() })? as u32;
+        db.delete_task(old)?;
+        db.delete_task(recent)?;
+        db.connection.execute(
+            "UPDATE Task SET deleted_at = datetime('now', '-40 days') WHERE id = ?1", (old,))?;
+
+        assert!(db.select_tasks(None)?.is_empty());
+        assert_eq!(db.purge_trash(Some(30))?, 2);
+        let names: Vec<String> = db.select_trashed_tasks()?.into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["recent"]);
+        Ok(())
+    }
+
+    #[test]
+    fn restoring_keeps_subtasks_trashed_on_their_own() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let parent = db.create_task(&CreateTask { name: String::from("parent"), ..Default::default() })? as u32;
+        let trashed = db.create_task(&CreateTask { name: String::from("trashed"), parent_id: Some(parent), ..Default::default() })? as u32;
+        db.create_task(&CreateTask { name: String::from("kept"), parent_id: Some(parent), ..Default::default() })?;
+        db.delete_task(trashed)?;
+        db.connection.execute(
+            "UPDATE Task SET deleted_at = datetime('now', '-1 hour') WHERE id = ?1", (trashed,))?;
+        db.delete_task(parent)?;
+
+        db.restore_task(parent)?;
+        let names: Vec<String> = db.select_tasks(None)?.into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["parent", "kept"]);
+        assert_eq!(db.select_trashed_tasks()?[0].id, trashed);
+        assert!(db.select_task_events(trashed)?.iter().all(|event| event.kind != TaskEventKind::Restore));
+        Ok(())
+    }
+
+    #[test]
+    fn unarchiving_keeps_subtasks_archived_on_their_own() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let parent = db.create_task(&CreateTask { name: String::from("parent"), ..Default::default() })? as u32;
+        let archived = db.create_task(&CreateTask { name: String::from("archived"), parent_id: Some(parent), ..Default::default() })? as u32;
+        db.create_task(&CreateTask { name: String::from("kept"), parent_id: Some(parent), ..Default::default() })?;
+        db.archive_task(archived)?;
+        db.connection.execute(
+            "UPDATE Task SET archived_at = datetime('now', '-1 hour') WHERE id = ?1", (archived,))?;
+        db.archive_task(parent)?;
+
+        db.unarchive_task(parent)?;
+        let names: Vec<String> = db.select_tasks(None)?.into_iter().map(|t| t.name).collect();
+        assert_eq!(names, ["parent", "kept"]);
+        assert_eq!(db.select_archived_tasks()?[0].id, archived);
+        assert!(db.select_task_events(archived)?.iter().all(|event| event.kind != TaskEventKind::Unarchive));
+        Ok(())
+    }
+
+    #[test]
+    fn task_changes_are_recorded() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let parent = db.create_task(&CreateTask { name: String::from("parent"), ..Default::default() })? as u32;
+        let task = db.create_task(&CreateTask { name: String::from("draft"), ..Default::default() })? as u32;
+        db.update_task(&UpdateTask {
+            id: task,
+            name: Some(String::from("final")),
+            priority: Some(Priority::None),
+            collapsed: Some(true),
+            ..Default::default()
+        })?;
+        db.update_task(&UpdateTask { id: task, completed: Some(1), parent_id: Some(Some(parent)), ..Default::default() })?;
+        db.delete_task(parent)?;
+        db.restore_task(parent)?;
+        db.archive_task(parent)?;
+        db.unarchive_task(parent)?;
+
+        let events: Vec<String> = db.select_task_events(task)?.iter().map(ToString::to_string).collect();
+        assert_eq!(events, [
+            "Created",
+            "Renamed from \"draft\" to \"final\"",
+            "Moved from the top level to task 1",
+            "Completed",
+            "Moved to the trash",
+            "Restored from the trash",
+            "Archived",
+            "Taken out of the archive",
+        ]);
+
+        // Purging a task drops its history with it
+        db.purge_task(parent)?;
+        assert!(db.select_task_events(task)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn search_index_follows_changes() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        let parent = db.create_task(&CreateTask { name: String::from("Release"), ..Default::default() })? as u32;
+        let child = db.create_task(&CreateTask {
+            name: String::from("Changelog"),
+            parent_id: Some(parent),
+            description: Some(String::from("List the deploy steps")),
+            ..Default::default()
+        })? as u32;
+        let deploy = db.create_task(&CreateTask { name: String::from("Deploy"), ..Default::default() })? as u32;
+
+        let ids = |query: &str| -> Result<Vec<u32>, Box<dyn Error>> {
+            Ok(db.search_tasks(query)?.into_iter().map(|hit| hit.task_id).collect())
+        };
+        // Name matches rank above description matches
+        assert_eq!(ids("deploy")?, [deploy, child]);
+
+        db.update_task(&UpdateTask { id: deploy, name: Some(String::from("Ship")), ..Default::default() })?;
+        assert_eq!(ids("deploy")?, [child]);
+
+        // Trashed subtasks stay indexed but drop out of the results until restored
+        db.delete_task(parent)?;
+        assert!(ids("deploy")?.is_empty());
+        assert_eq!(ids("ship")?, [deploy]);
+        db.restore_task(parent)?;
+        assert_eq!(ids("deploy")?, [child]);
+
+        // Purging removes them from the index
+        db.delete_task(parent)?;
+        db.purge_task(parent)?;
+        let indexed: u32 = db.connection.query_row(
+            "SELECT count(*) FROM TaskSearch WHERE TaskSearch MATCH 'deploy'", [], |row| row.get(0))?;
+        assert_eq!(indexed, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn siblings_sorted_by_priority() -> Result<(), Box<dyn Error>> {
+        let db = memory_db()?;
+        for (name, priority) in [("a", Priority::Low), ("b", Priority::Urgent), ("c", Priority::Low)] {
+            db.create_task(&CreateTask { name: name.to_string(), priority, ..Default::default() })?;
+        }
+
+        let names: Vec<String> = db.select_tasks_hierarchy(None)?
+            .into_iter()
+            .map(|t| t.task.name)
+            .collect();
+        assert_eq!(names, ["b", "a", "c"]);
+
+        Ok(())
+    }
+
 }
\ No newline at end of file
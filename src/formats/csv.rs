@@ -0,0 +1,313 @@
+//! CSV with one row per task, in the order of the flattened tree.
+//!
+//! The export has a header row naming the fields of [`Field`]. On import the
+//! columns are looked up by header, which a [`ColumnMapping`] can redirect to
+//! other names. The hierarchy comes from the `parent_id` column if there is
+//! one, else from `path`, else from `depth`; without any of them all rows
+//! become top level tasks.
+
+use std::error::Error;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{
+    formats::{ImportedTask, LinkedTask, nest}, services::navigation_service::NavigationService, task::*
+};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Id,
+    ParentId,
+    Depth,
+    Path,
+    Name,
+    Completed,
+    Description,
+    Priority,
+    DueDate,
+    Tags,
+    Recurrence,
+    CreationDate,
+    CompletedAt,
+}
+
+impl Field {
+    pub const ALL: [Field; 13] = [
+        Field::Id, Field::ParentId, Field::Depth, Field::Path, Field::Name, Field::Completed,
+        Field::Description, Field::Priority, Field::DueDate, Field::Tags, Field::Recurrence,
+        Field::CreationDate, Field::CompletedAt,
+    ];
+
+    /// The header of the column in exports.
+    pub fn header(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::ParentId => "parent_id",
+            Field::Depth => "depth",
+            Field::Path => "path",
+            Field::Name => "name",
+            Field::Completed => "completed",
+            Field::Description => "description",
+            Field::Priority => "priority",
+            Field::DueDate => "due_date",
+            Field::Tags => "tags",
+            Field::Recurrence => "recurrence",
+            Field::CreationDate => "creation_date",
+            Field::CompletedAt => "completed_at",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self, Box<dyn Error>> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.into_iter().find(|f| f.header() == name).ok_or_else(|| {
+            let headers: Vec<&str> = Self::ALL.iter().map(|f| f.header()).collect();
+            format!("Unknown field '{}', expected one of: {}", name, headers.join(", ")).into()
+        })
+    }
+}
+
+/// Which CSV header holds each field. Fields that aren't mapped are read from
+/// the column named like in exports.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnMapping {
+    columns: Vec<(Field, String)>,
+}
+
+impl ColumnMapping {
+    /// Parses `field=Header` pairs, e.g. `name=Title,due_date=Deadline`.
+    pub fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+        let mut mapping = Self::default();
+        for pair in input.split(',').filter(|p| !p.trim().is_empty()) {
+            let (field, header) = pair.split_once('=')
+                .ok_or(format!("Invalid column mapping '{}', expected FIELD=HEADER", pair))?;
+            mapping = mapping.map(Field::parse(field)?, header.trim());
+        }
+        Ok(mapping)
+    }
+
+    pub fn map(mut self, field: Field, header: &str) -> Self {
+        self.columns.retain(|(f, _)| *f != field);
+        self.columns.push((field, header.to_string()));
+        self
+    }
+
+    fn header(&self, field: Field) -> &str {
+        self.columns.iter()
+            .find(|(f, _)| *f == field)
+            .map(|(_, header)| header.as_str())
+            .unwrap_or(field.header())
+    }
+}
+
+pub fn export(tasks: &[TaskWithKids]) -> Result<String, Box<dyn Error>> {
+    let mut writer = ::csv::Writer::from_writer(Vec::new());
+    writer.write_record(Field::ALL.map(Field::header))?;
+
+    for flat in NavigationService::flatten_all(tasks) {
+        let task = &flat.task;
+        let path: Vec<String> = flat.parent_path.iter().map(u32::to_string).collect();
+        writer.write_record([
+            task.id.to_string(),
+            task.parent_id.map(|id| id.to_string()).unwrap_or_default(),
+            flat.depth.to_string(),
+            path.join("/"),
+            task.name.clone(),
+            task.completed.to_string(),
+            task.description.clone().unwrap_or_default(),
+            match task.priority {
+                Priority::None => String::new(),
+                priority => priority.label().to_lowercase(),
+            },
+            task.due_date.map(|due| due.to_db_string()).unwrap_or_default(),
+            task.tags.join(","),
+            task.recurrence.as_ref().map(Recurrence::to_rrule).unwrap_or_default(),
+            task.creation_date.format(DB_DATE_FORMAT).to_string(),
+            task.completed_at.map(|date| date.format(DB_DATE_FORMAT).to_string()).unwrap_or_default(),
+        ])?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+fn parse_bool(value: &str) -> Result<bool, Box<dyn Error>> {
+    match value.to_lowercase().as_str() {
+        "" | "false" | "no" | "0" | "open" => Ok(false),
+        "true" | "yes" | "1" | "x" | "done" | "completed" => Ok(true),
+        _ => Err(format!("Invalid completion '{}', expected true or false", value).into()),
+    }
+}
+
+fn parse_date_time(value: &str) -> Result<NaiveDateTime, Box<dyn Error>> {
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(value, DB_DATE_FORMAT) {
+        return Ok(date_time);
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{}', expected YYYY-MM-DD [HH:MM:SS]", value))?;
+    Ok(date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+/// Column indices of the mapped fields in one file.
+struct Columns {
+    indices: Vec<(Field, usize)>,
+}
+
+impl Columns {
+    fn find(headers: &::csv::StringRecord, mapping: &ColumnMapping) -> Self {
+        let indices = Field::ALL.into_iter()
+            .filter_map(|field| {
+                let header = mapping.header(field);
+                headers.iter()
+                    .position(|h| h.trim().eq_ignore_ascii_case(header.trim()))
+                    .map(|index| (field, index))
+            })
+            .collect();
+        Self { indices }
+    }
+
+    fn has(&self, field: Field) -> bool {
+        self.indices.iter().any(|(f, _)| *f == field)
+    }
+
+    /// The trimmed value of `field` in `row`, if the column exists and isn't empty.
+    fn get<'a>(&self, row: &'a ::csv::StringRecord, field: Field) -> Option<&'a str> {
+        let (_, index) = self.indices.iter().find(|(f, _)| *f == field)?;
+        row.get(*index).map(str::trim).filter(|value| !value.is_empty())
+    }
+}
+
+pub fn import(input: &str) -> Result<Vec<ImportedTask>, Box<dyn Error>> {
+    import_with(input, &ColumnMapping::default())
+}
+
+/// Imports with custom column names. Every invalid row is reported with its
+/// line number, and nothing is imported unless all rows are valid.
+pub fn import_with(input: &str, mapping: &ColumnMapping) -> Result<Vec<ImportedTask>, Box<dyn Error>> {
+    let mut reader = ::csv::ReaderBuilder::new().flexible(true).from_reader(input.as_bytes());
+    let columns = Columns::find(reader.headers()?, mapping);
+    if !columns.has(Field::Name) {
+        return Err(format!("CSV has no '{}' column for task names", mapping.header(Field::Name)).into());
+    }
+
+    let mut tasks = Vec::new();
+    let mut errors = Vec::new();
+    // Open ancestors as (depth, key) when nesting by depth
+    let mut ancestors: Vec<(u32, String)> = Vec::new();
+
+    for row in reader.records() {
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                errors.push(e.to_string());
+                continue;
+            }
+        };
+        let line = row.position().map(|p| p.line()).unwrap_or_default();
+        match parse_row(&row, &columns, line, &mut ancestors) {
+            Ok(task) => tasks.push(task),
+            Err(e) => errors.push(format!("Line {}: {}", line, e)),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(format!("{} invalid rows:\n{}", errors.len(), errors.join("\n")).into());
+    }
+    Ok(nest(tasks))
+}
+
+fn parse_row(
+    row: &::csv::StringRecord,
+    columns: &Columns,
+    line: u64,
+    ancestors: &mut Vec<(u32, String)>,
+) -> Result<LinkedTask<String>, Box<dyn Error>> {
+    let name = columns.get(row, Field::Name).ok_or("Missing task name")?;
+    let id = columns.get(row, Field::Id);
+
+    let task = CreateTask {
+        id: id.and_then(|id| id.parse().ok()),
+        name: name.to_string(),
+        description: columns.get(row, Field::Description).map(str::to_string),
+        completed: columns.get(row, Field::Completed).map(parse_bool).transpose()?.unwrap_or(false),
+        creation_date: columns.get(row, Field::CreationDate).map(parse_date_time).transpose()?,
+        completed_at: columns.get(row, Field::CompletedAt).map(parse_date_time).transpose()?,
+        due_date: columns.get(row, Field::DueDate).map(DueDate::parse).transpose()?,
+        priority: columns.get(row, Field::Priority).map(Priority::parse).transpose()?.unwrap_or_default(),
+        recurrence: columns.get(row, Field::Recurrence)
+            .map(|rule| Recurrence::from_rrule(rule).or_else(|_| Recurrence::parse(rule)))
+            .transpose()?,
+        ..Default::default()
+    };
+    let tags = columns.get(row, Field::Tags).map(Tag::parse_list).transpose()?.unwrap_or_default();
+
+    let (key, parent) = if columns.has(Field::ParentId) {
+        (id.map(str::to_string), columns.get(row, Field::ParentId).map(str::to_string))
+    } else if columns.has(Field::Path) {
+        let path: Vec<&str> = columns.get(row, Field::Path).unwrap_or_default().split('/').collect();
+        let key = id.or(path.last().copied()).map(str::to_string);
+        (key, path.len().checked_sub(2).map(|i| path[i].to_string()))
+    } else if columns.has(Field::Depth) {
+        let depth: u32 = columns.get(row, Field::Depth).unwrap_or("0").parse()
+            .map_err(|_| "Depth must be a number")?;
+        while ancestors.last().is_some_and(|(d, _)| *d >= depth) {
+            ancestors.pop();
+        }
+        let key = format!("line {}", line);
+        let parent = ancestors.last().map(|(_, key)| key.clone());
+        ancestors.push((depth, key.clone()));
+        (Some(key), parent)
+    } else {
+        (None, None)
+    };
+
+    Ok(LinkedTask { task: ImportedTask { task, tags, subtasks: Vec::new() }, key, parent })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mapped_columns_and_depth() -> Result<(), Box<dyn Error>> {
+        let mapping = ColumnMapping::parse("name=Action item, due_date=Deadline, depth=Level")?;
+        let tasks = import_with("\
+Action item,Owner,Deadline,Level,Done
+Prepare offsite,Ann,2025-06-01,0,no
+Book rooms,Bob,,1,yes
+Send invites,Ann,,1,
+Retro,Cid,,0,
+", &mapping.map(Field::Completed, "Done"))?;
+
+        assert_eq!(tasks.len(), 2);
+        let offsite = &tasks[0];
+        assert_eq!(offsite.task.due_date, Some(DueDate::parse("2025-06-01")?));
+        let names: Vec<&str> = offsite.subtasks.iter().map(|t| t.task.name.as_str()).collect();
+        assert_eq!(names, ["Book rooms", "Send invites"]);
+        assert!(offsite.subtasks[0].task.completed);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_rows_are_reported() {
+        let error = import("\
+name,priority,due_date
+Fine,high,
+Bad priority,extreme,
+,low,
+Bad date,,tomorrow-ish
+").unwrap_err().to_string();
+
+        assert!(error.starts_with("3 invalid rows"));
+        assert!(error.contains("Line 3: "));
+        assert!(error.contains("Line 4: Missing task name"));
+        assert!(error.contains("Line 5: "));
+        assert!(!error.contains("Line 2"));
+    }
+
+    #[test]
+    fn missing_name_column() {
+        assert!(import("title\nSomething\n").is_err());
+        assert!(ColumnMapping::parse("title=Title").is_err());
+    }
+}
@@ -0,0 +1,431 @@
+//! iCalendar (RFC 5545) files with one `VTODO` per task.
+//!
+//! The `UID` is the task's uuid, so importing an edited export again updates
+//! the same tasks, and `X-TODOLIST-ID` keeps its id. The hierarchy is kept
+//! with `RELATED-TO;RELTYPE=PARENT` pointing at the parent's `UID`. Recurrence
+//! rules clients can't understand (repeating a number of days after
+//! completion) are written as `X-TODOLIST-RRULE`. Components other than
+//! `VTODO` and rules we can't represent are skipped on import.
+
+use std::error::Error;
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+
+use crate::{formats::{ImportedTask, LinkedTask, nest}, task::*};
+
+
+const UID_SUFFIX: &str = "@todolist";
+const DATE_FORMAT: &str = "%Y%m%d";
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+const LINE_LIMIT: usize = 75;
+
+fn priority_to_ical(priority: Priority) -> Option<u8> {
+    match priority {
+        Priority::None => None,
+        Priority::Low => Some(9),
+        Priority::Medium => Some(5),
+        Priority::High => Some(3),
+        Priority::Urgent => Some(1),
+    }
+}
+
+fn priority_from_ical(value: u8) -> Priority {
+    match value {
+        0 => Priority::None,
+        1 => Priority::Urgent,
+        2..=4 => Priority::High,
+        5 => Priority::Medium,
+        _ => Priority::Low,
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Appends a content line, folded so that no line exceeds 75 octets.
+fn push_line(output: &mut String, line: &str) {
+    let mut rest = line;
+    let mut limit = LINE_LIMIT;
+    loop {
+        let mut end = rest.len().min(limit);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.push_str(&rest[..end]);
+        output.push_str("\r\n");
+        rest = &rest[end..];
+        if rest.is_empty() {
+            break;
+        }
+        output.push(' ');
+        limit = LINE_LIMIT - 1;
+    }
+}
+
+/// Tasks without a uuid, and files from older versions, use `{id}@todolist`.
+fn uid(task: &Task) -> String {
+    task.uuid.clone().unwrap_or_else(|| format!("{}{}", task.id, UID_SUFFIX))
+}
+
+pub fn export(tasks: &[TaskWithKids]) -> Result<String, Box<dyn Error>> {
+    let stamp = format!("{}Z", Utc::now().format(DATE_TIME_FORMAT));
+    let mut output = String::new();
+    push_line(&mut output, "BEGIN:VCALENDAR");
+    push_line(&mut output, "VERSION:2.0");
+    push_line(&mut output, "PRODID:-//todolist//todolist//EN");
+    for task in tasks {
+        export_tree(task, None, &stamp, &mut output);
+    }
+    push_line(&mut output, "END:VCALENDAR");
+    Ok(output)
+}
+
+fn export_tree(node: &TaskWithKids, parent_uid: Option<&str>, stamp: &str, output: &mut String) {
+    let task = &node.task;
+    let uid = uid(task);
+    // Creation and completion times are stored in UTC
+    let created = format!("{}Z", task.creation_date.format(DATE_TIME_FORMAT));
+
+    push_line(output, "BEGIN:VTODO");
+    push_line(output, &format!("UID:{}", uid));
+    push_line(output, &format!("X-TODOLIST-ID:{}", task.id));
+    push_line(output, &format!("DTSTAMP:{}", stamp));
+    push_line(output, &format!("CREATED:{}", created));
+    push_line(output, &format!("SUMMARY:{}", escape(&task.name)));
+    if let Some(description) = &task.description {
+        push_line(output, &format!("DESCRIPTION:{}", escape(description)));
+    }
+    let status = if task.completed { "COMPLETED" } else { "NEEDS-ACTION" };
+    push_line(output, &format!("STATUS:{}", status));
+    if let Some(completed_at) = task.completed_at.filter(|_| task.completed) {
+        push_line(output, &format!("COMPLETED:{}Z", completed_at.format(DATE_TIME_FORMAT)));
+    }
+    if let Some(priority) = priority_to_ical(task.priority) {
+        push_line(output, &format!("PRIORITY:{}", priority));
+    }
+    match task.due_date {
+        Some(DueDate::Date(date)) => push_line(output, &format!("DUE;VALUE=DATE:{}", date.format(DATE_FORMAT))),
+        Some(DueDate::DateTime(date_time)) => push_line(output, &format!("DUE:{}", date_time.format(DATE_TIME_FORMAT))),
+        None => {}
+    }
+    match &task.recurrence {
+        Some(recurrence @ Recurrence::AfterCompletion(_)) => {
+            push_line(output, &format!("X-TODOLIST-RRULE:{}", recurrence.to_rrule()));
+        }
+        Some(recurrence) => push_line(output, &format!("RRULE:{}", recurrence.to_rrule())),
+        None => {}
+    }
+    if !task.tags.is_empty() {
+        let tags: Vec<String> = task.tags.iter().map(|tag| escape(tag)).collect();
+        push_line(output, &format!("CATEGORIES:{}", tags.join(",")));
+    }
+    if let Some(parent_uid) = parent_uid {
+        push_line(output, &format!("RELATED-TO;RELTYPE=PARENT:{}", parent_uid));
+    }
+    push_line(output, "END:VTODO");
+
+    for subtask in &node.subtasks {
+        export_tree(subtask, Some(&uid), stamp, output);
+    }
+}
+
+/// A content line: `NAME;PARAM=VALUE:value`.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Self> {
+        let mut quoted = false;
+        let colon = line.char_indices().find(|&(_, c)| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == ':' && !quoted
+        })?.0;
+
+        let mut parts = line[..colon].split(';');
+        let name = parts.next()?.trim().to_uppercase();
+        let params = parts
+            .filter_map(|param| param.split_once('='))
+            .map(|(key, value)| (key.trim().to_uppercase(), value.trim_matches('"').to_string()))
+            .collect();
+        Some(Self { name, params, value: line[colon + 1..].to_string() })
+    }
+
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Joins folded lines back together.
+fn unfold(input: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in input.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Parses `DATE` and `DATE-TIME` values. UTC times are converted to local
+/// time for due dates, times with a `TZID` are taken as they are.
+fn parse_due(property: &Property) -> Result<DueDate, Box<dyn Error>> {
+    let value = property.value.trim();
+    if property.param("VALUE") == Some("DATE") || !value.contains('T') {
+        return Ok(DueDate::Date(NaiveDate::parse_from_str(value, DATE_FORMAT)?));
+    }
+    let date_time = parse_date_time(value)?;
+    match value.ends_with('Z') {
+        true => Ok(DueDate::DateTime(utc_to_local(date_time))),
+        false => Ok(DueDate::DateTime(date_time)),
+    }
+}
+
+fn parse_date_time(value: &str) -> Result<NaiveDateTime, Box<dyn Error>> {
+    let value = value.trim().trim_end_matches('Z');
+    if let Ok(date_time) = NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT) {
+        return Ok(date_time);
+    }
+    let date = NaiveDate::parse_from_str(value, DATE_FORMAT)
+        .map_err(|_| format!("Invalid date '{}'", value))?;
+    Ok(date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+#[derive(Default)]
+struct Todo {
+    uid: Option<String>,
+    id: Option<u32>,
+    parent: Option<String>,
+    task: CreateTask,
+    tags: Vec<String>,
+    rrule: Option<String>,
+    custom_rrule: Option<String>,
+}
+
+impl Todo {
+    fn apply(&mut self, property: &Property) -> Result<(), Box<dyn Error>> {
+        let value = property.value.as_str();
+        match property.name.as_str() {
+            "UID" => self.uid = Some(value.to_string()),
+            "X-TODOLIST-ID" => self.id = Some(value.trim().parse().map_err(|_| format!("Invalid id '{}'", value))?),
+            "SUMMARY" => self.task.name = unescape(value).trim().to_string(),
+            "DESCRIPTION" if !value.is_empty() => self.task.description = Some(unescape(value)),
+            "STATUS" => self.task.completed = value.eq_ignore_ascii_case("COMPLETED"),
+            "COMPLETED" => {
+                self.task.completed = true;
+                let date_time = parse_date_time(value)?;
+                self.task.completed_at = Some(match value.ends_with('Z') {
+                    true => date_time,
+                    false => local_to_utc(date_time),
+                });
+            }
+            "CREATED" => self.task.creation_date = Some(parse_date_time(value)?),
+            "DUE" => self.task.due_date = Some(parse_due(property)?),
+            "PRIORITY" => self.task.priority = priority_from_ical(value.trim().parse()?),
+            "RRULE" => self.rrule = Some(value.to_string()),
+            "X-TODOLIST-RRULE" => self.custom_rrule = Some(value.to_string()),
+            "CATEGORIES" => {
+                for tag in split_list(value) {
+                    let tag = Tag::normalize(&tag.replace(' ', "-"))?;
+                    if !self.tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                        self.tags.push(tag);
+                    }
+                }
+            }
+            "RELATED-TO" if property.param("RELTYPE").unwrap_or("PARENT").eq_ignore_ascii_case("PARENT") => {
+                self.parent = Some(value.to_string());
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn into_linked(self) -> Result<LinkedTask<String>, Box<dyn Error>> {
+        let mut task = self.task;
+        if task.name.is_empty() {
+            return Err("Task without a SUMMARY".into());
+        }
+        let legacy_id = self.uid.as_deref()
+            .and_then(|uid| uid.strip_suffix(UID_SUFFIX))
+            .and_then(|id| id.parse().ok());
+        task.id = self.id.or(legacy_id);
+        if legacy_id.is_none() {
+            task.uuid = self.uid.clone();
+        }
+        task.recurrence = self.custom_rrule.or(self.rrule)
+            .and_then(|rule| Recurrence::from_rrule(&rule).ok());
+
+        Ok(LinkedTask {
+            task: ImportedTask { task, tags: self.tags, subtasks: Vec::new() },
+            key: self.uid,
+            parent: self.parent,
+        })
+    }
+}
+
+/// Splits a comma separated value, honouring `\,` escapes.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => items.last_mut().unwrap().push_str(&unescape(&format!("\\{}", chars.next().unwrap_or('\\')))),
+            ',' => items.push(String::new()),
+            c => items.last_mut().unwrap().push(c),
+        }
+    }
+    items.into_iter().map(|item| item.trim().to_string()).filter(|item| !item.is_empty()).collect()
+}
+
+pub fn import(input: &str) -> Result<Vec<ImportedTask>, Box<dyn Error>> {
+    let mut todos = Vec::new();
+    let mut components: Vec<String> = Vec::new();
+    let mut current: Option<Todo> = None;
+
+    for line in unfold(input) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let property = Property::parse(&line).ok_or(format!("Invalid iCalendar line '{}'", line))?;
+        match property.name.as_str() {
+            "BEGIN" => {
+                let component = property.value.trim().to_uppercase();
+                if component == "VTODO" && components.last().is_none_or(|c| c == "VCALENDAR") {
+                    current = Some(Todo::default());
+                }
+                components.push(component);
+            }
+            "END" => {
+                if components.pop().as_deref() == Some("VTODO")
+                    && let Some(todo) = current.take() {
+                    let linked = todo.into_linked()
+                        .map_err(|e| format!("VTODO #{}: {}", todos.len() + 1, e))?;
+                    todos.push(linked);
+                }
+            }
+            _ => {
+                if components.last().map(String::as_str) == Some("VTODO")
+                    && let Some(todo) = current.as_mut() {
+                    todo.apply(&property)
+                        .map_err(|e| format!("{} in '{}'", e, line))?;
+                }
+            }
+        }
+    }
+
+    Ok(nest(todos))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn foreign_todos() -> Result<(), Box<dyn Error>> {
+        let tasks = import("\
+BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:event\r
+SUMMARY:Not a task\r
+END:VEVENT\r
+BEGIN:VTODO\r
+UID:child\r
+SUMMARY:Buy milk\\, eggs\r
+RELATED-TO:parent\r
+STATUS:COMPLETED\r
+COMPLETED:20250228T181500\r
+END:VTODO\r
+BEGIN:VTODO\r
+UID:parent\r
+SUMMARY:Groceries\r
+DESCRIPTION:A long description that some clients fold over\r
+  several lines\r
+DUE;VALUE=DATE:20250301\r
+PRIORITY:2\r
+CATEGORIES:home,errands\r
+RRULE:FREQ=YEARLY\r
+BEGIN:VALARM\r
+DESCRIPTION:Reminder\r
+END:VALARM\r
+END:VTODO\r
+END:VCALENDAR\r
+")?;
+        assert_eq!(tasks.len(), 1);
+        let groceries = &tasks[0];
+        assert_eq!(groceries.task.id, None);
+        assert_eq!(groceries.task.uuid.as_deref(), Some("parent"));
+        assert_eq!(groceries.task.description.as_deref(),
+            Some("A long description that some clients fold over several lines"));
+        assert_eq!(groceries.task.due_date, Some(DueDate::parse("2025-03-01")?));
+        assert_eq!(groceries.task.priority, Priority::High);
+        assert_eq!(groceries.task.recurrence, None);
+        assert_eq!(groceries.tags, ["home", "errands"]);
+
+        assert_eq!(groceries.subtasks[0].task.name, "Buy milk, eggs");
+        assert!(groceries.subtasks[0].task.completed);
+        assert_eq!(groceries.subtasks[0].task.completed_at, Some(local_to_utc(parse_date_time("20250228T181500")?)));
+        Ok(())
+    }
+
+    #[test]
+    fn uid_is_the_uuid() -> Result<(), Box<dyn Error>> {
+        let task = |id, uuid: Option<&str>| TaskWithKids {
+            task: Task { id, name: format!("Task {}", id), uuid: uuid.map(str::to_string), ..Default::default() },
+            subtasks: Vec::new(),
+        };
+        let exported = export(&[task(1, Some("5f0c7e8a-1d2b-4c3d-9e4f-0a1b2c3d4e5f")), task(2, None)])?;
+        assert!(exported.contains("UID:5f0c7e8a-1d2b-4c3d-9e4f-0a1b2c3d4e5f\r\nX-TODOLIST-ID:1\r\n"));
+        assert!(exported.contains("UID:2@todolist\r\n"));
+
+        let tasks = import(&exported)?;
+        assert_eq!(tasks[0].task.uuid.as_deref(), Some("5f0c7e8a-1d2b-4c3d-9e4f-0a1b2c3d4e5f"));
+        assert_eq!(tasks[1].task.uuid, None);
+        Ok(())
+    }
+
+    #[test]
+    fn long_lines_are_folded() {
+        let mut output = String::new();
+        let line = format!("SUMMARY:{}", "ä".repeat(100));
+        push_line(&mut output, &line);
+
+        assert!(output.split("\r\n").all(|l| l.len() <= LINE_LIMIT));
+        assert_eq!(unfold(&output), [line]);
+    }
+}
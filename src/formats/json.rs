@@ -0,0 +1,135 @@
+use std::error::Error;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{formats::ImportedTask, task::*};
+
+
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct JsonExport {
+    version: u32,
+    tasks: Vec<JsonTask>,
+}
+
+/// Accepts the full export document or just its `tasks` array.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonDocument {
+    Export(JsonExport),
+    Tasks(Vec<JsonTask>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonTask {
+    #[serde(default)]
+    id: Option<u32>,
+    /// Lets a later import update this task instead of adding a copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uuid: Option<String>,
+    name: String,
+    #[serde(default)]
+    completed: bool,
+    #[serde(default)]
+    description: Option<String>,
+    /// `YYYY-MM-DD HH:MM:SS`, UTC
+    #[serde(default)]
+    creation_date: Option<String>,
+    /// `YYYY-MM-DD HH:MM:SS`, UTC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// RRULE-style rule, e.g. `FREQ=WEEKLY;BYDAY=MO`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recurrence: Option<String>,
+    /// Whether the subtasks are hidden in the interface
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    collapsed: bool,
+    #[serde(default)]
+    subtasks: Vec<JsonTask>,
+}
+
+impl JsonTask {
+    fn from_tree(node: &TaskWithKids) -> Self {
+        let task = &node.task;
+        Self {
+            id: Some(task.id),
+            uuid: task.uuid.clone(),
+            name: task.name.clone(),
+            completed: task.completed,
+            description: task.description.clone(),
+            creation_date: Some(task.creation_date.format(DB_DATE_FORMAT).to_string()),
+            completed_at: task.completed_at.map(|date| date.format(DB_DATE_FORMAT).to_string()),
+            due_date: task.due_date.map(|due| due.to_db_string()),
+            priority: match task.priority {
+                Priority::None => None,
+                priority => Some(priority.label().to_lowercase()),
+            },
+            tags: task.tags.clone(),
+            recurrence: task.recurrence.as_ref().map(Recurrence::to_rrule),
+            collapsed: task.collapsed,
+            subtasks: node.subtasks.iter().map(Self::from_tree).collect(),
+        }
+    }
+
+    fn into_imported(self) -> Result<ImportedTask, Box<dyn Error>> {
+        if self.name.trim().is_empty() {
+            return Err("Task without a name in JSON input".into());
+        }
+        let tags = self.tags.iter()
+            .map(|tag| Tag::normalize(tag))
+            .collect::<Result<Vec<_>, _>>()?;
+        let subtasks = self.subtasks.into_iter()
+            .map(JsonTask::into_imported)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ImportedTask {
+            task: CreateTask {
+                id: self.id,
+                name: self.name,
+                description: self.description,
+                completed: self.completed,
+                creation_date: self.creation_date.as_deref()
+                    .map(|date| NaiveDateTime::parse_from_str(date, DB_DATE_FORMAT))
+                    .transpose()?,
+                completed_at: self.completed_at.as_deref()
+                    .map(|date| NaiveDateTime::parse_from_str(date, DB_DATE_FORMAT))
+                    .transpose()?,
+                due_date: self.due_date.as_deref().map(DueDate::parse).transpose()?,
+                priority: self.priority.as_deref().map(Priority::parse).transpose()?.unwrap_or_default(),
+                recurrence: self.recurrence.as_deref().map(Recurrence::from_rrule).transpose()?,
+                uuid: self.uuid,
+                collapsed: self.collapsed,
+                ..Default::default()
+            },
+            tags,
+            subtasks,
+        })
+    }
+}
+
+pub fn export(tasks: &[TaskWithKids]) -> Result<String, Box<dyn Error>> {
+    let document = JsonExport {
+        version: VERSION,
+        tasks: tasks.iter().map(JsonTask::from_tree).collect(),
+    };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+pub fn import(input: &str) -> Result<Vec<ImportedTask>, Box<dyn Error>> {
+    let tasks = match serde_json::from_str(input)? {
+        JsonDocument::Export(export) if export.version > VERSION => {
+            return Err(format!("Unsupported JSON export version {}", export.version).into());
+        }
+        JsonDocument::Export(export) => export.tasks,
+        JsonDocument::Tasks(tasks) => tasks,
+    };
+    tasks.into_iter().map(JsonTask::into_imported).collect()
+}
@@ -0,0 +1,211 @@
+//! GitHub-style Markdown checklists:
+//!
+//! ```text
+//! - [ ] Project
+//!
+//!   Description of the project
+//!   - [x] Done step
+//! ```
+//!
+//! Nesting follows the indentation of the list items, and lines indented under
+//! an item that aren't list items themselves make up its description. Other
+//! lines (headings, text around the list) are ignored on import.
+
+use std::error::Error;
+
+use crate::{formats::ImportedTask, task::*};
+
+
+const INDENT: &str = "  ";
+
+pub fn export(tasks: &[TaskWithKids]) -> Result<String, Box<dyn Error>> {
+    let mut output = String::new();
+    for task in tasks {
+        export_tree(task, 0, &mut output);
+    }
+    Ok(output)
+}
+
+fn export_tree(node: &TaskWithKids, depth: usize, output: &mut String) {
+    let indent = INDENT.repeat(depth);
+    let check = if node.task.completed { 'x' } else { ' ' };
+    output.push_str(&format!("{}- [{}] {}\n", indent, check, node.task.name));
+
+    if let Some(description) = &node.task.description {
+        output.push('\n');
+        for line in description.lines() {
+            if line.trim().is_empty() {
+                output.push('\n');
+            } else {
+                output.push_str(&format!("{}{}{}\n", indent, INDENT, escape(line)));
+            }
+        }
+    }
+
+    for subtask in &node.subtasks {
+        export_tree(subtask, depth + 1, output);
+    }
+}
+
+/// Keeps description lines from being read back as list items.
+fn escape(line: &str) -> String {
+    match line.trim_start().chars().next() {
+        Some('-' | '*' | '+' | '\\') => {
+            let trimmed = line.trim_start();
+            format!("{}\\{}", &line[..line.len() - trimmed.len()], trimmed)
+        }
+        _ => line.to_string(),
+    }
+}
+
+fn unescape(line: &str) -> &str {
+    match line.strip_prefix('\\') {
+        Some(rest) if rest.starts_with(['-', '*', '+', '\\']) => rest,
+        _ => line,
+    }
+}
+
+/// A list item whose subtasks may still follow.
+struct OpenItem {
+    indent: usize,
+    content_indent: usize,
+    task: ImportedTask,
+}
+
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Splits `- [x] name` into its completion mark and name.
+fn parse_item(text: &str) -> Option<(bool, &str)> {
+    let rest = text.strip_prefix(['-', '*', '+'])?;
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    let rest = rest.trim_start();
+    let item = match rest.get(..3) {
+        Some("[ ]") => (false, &rest[3..]),
+        Some("[x]" | "[X]") => (true, &rest[3..]),
+        _ => (false, rest),
+    };
+    Some((item.0, item.1.trim()))
+}
+
+/// Moves the innermost open item into its parent, or into `roots` if it's
+/// a top level item.
+fn close_item(stack: &mut Vec<OpenItem>, roots: &mut Vec<ImportedTask>) {
+    if let Some(item) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.task.subtasks.push(item.task),
+            None => roots.push(item.task),
+        }
+    }
+}
+
+pub fn import(input: &str) -> Result<Vec<ImportedTask>, Box<dyn Error>> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<OpenItem> = Vec::new();
+    let mut blank_lines = 0;
+
+    for (number, line) in input.lines().enumerate() {
+        let text = line.trim();
+        if text.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        let indent = indent_width(line);
+
+        if let Some((completed, name)) = parse_item(text) {
+            if name.is_empty() {
+                return Err(format!("Line {}: List item without text", number + 1).into());
+            }
+            while stack.last().is_some_and(|item| item.indent >= indent) {
+                close_item(&mut stack, &mut roots);
+            }
+            let task = CreateTask { name: name.to_string(), completed, ..Default::default() };
+            let content_indent = indent + line.trim_start().len() - line.trim_start()[1..].trim_start().len();
+            stack.push(OpenItem { indent, content_indent, task: ImportedTask { task, ..Default::default() } });
+        } else if let Some(item) = stack.iter_mut().rev().find(|item| item.content_indent <= indent) {
+            let text = unescape(text);
+            let description = &mut item.task.task.description;
+            match description {
+                Some(description) => {
+                    description.push_str(&"\n".repeat(blank_lines + 1));
+                    description.push_str(text);
+                }
+                None => *description = Some(text.to_string()),
+            }
+        }
+        blank_lines = 0;
+    }
+
+    while !stack.is_empty() {
+        close_item(&mut stack, &mut roots);
+    }
+    Ok(roots)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_checklist() -> Result<(), Box<dyn Error>> {
+        let tasks = import("\
+# Meeting notes
+
+- [ ] Release
+  Ship it
+
+  on Friday
+  - [x] Changelog
+    * [ ] Link issues
+  - [X] Tag
+- Follow up
+\t- [ ] Tab indented
+")?;
+        assert_eq!(tasks.len(), 2);
+
+        let release = &tasks[0];
+        assert_eq!(release.task.description.as_deref(), Some("Ship it\n\non Friday"));
+        let names: Vec<&str> = release.subtasks.iter().map(|t| t.task.name.as_str()).collect();
+        assert_eq!(names, ["Changelog", "Tag"]);
+        assert!(release.subtasks.iter().all(|t| t.task.completed));
+        assert_eq!(release.subtasks[0].subtasks[0].task.name, "Link issues");
+
+        assert!(!tasks[1].task.completed);
+        assert_eq!(tasks[1].subtasks[0].task.name, "Tab indented");
+        Ok(())
+    }
+
+    #[test]
+    fn descriptions_follow_the_task() -> Result<(), Box<dyn Error>> {
+        let step = Task { id: 2, name: String::from("Step"), ..Default::default() };
+        let project = TaskWithKids {
+            task: Task {
+                id: 1,
+                name: String::from("Project"),
+                description: Some(String::from("Line one\nLine two")),
+                ..Default::default()
+            },
+            subtasks: vec![TaskWithKids { task: step, subtasks: Vec::new() }],
+        };
+        assert_eq!(export(&[project])?, "- [ ] Project\n\n  Line one\n  Line two\n  - [ ] Step\n");
+        Ok(())
+    }
+
+    #[test]
+    fn description_list_markers_are_escaped() -> Result<(), Box<dyn Error>> {
+        assert_eq!(escape("  - not a subtask"), "  \\- not a subtask");
+        assert_eq!(escape("plain"), "plain");
+
+        let tasks = import("- [ ] Task\n  \\- not a subtask\n  \\\\ kept")?;
+        assert!(tasks[0].subtasks.is_empty());
+        assert_eq!(tasks[0].task.description.as_deref(), Some("- not a subtask\n\\ kept"));
+        Ok(())
+    }
+}
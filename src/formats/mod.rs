@@ -0,0 +1,188 @@
+use std::{collections::HashMap, error::Error, hash::Hash, path::Path};
+
+use crate::task::{CreateTask, TaskWithKids};
+
+pub mod csv;
+pub mod ical;
+pub mod json;
+pub mod markdown;
+pub mod org;
+pub mod taskwarrior;
+pub mod todotxt;
+
+
+/// A task read from an external format, together with its subtasks.
+#[derive(Debug, Clone, Default)]
+pub struct ImportedTask {
+    pub task: CreateTask,
+    pub tags: Vec<String>,
+    pub subtasks: Vec<ImportedTask>,
+}
+
+impl ImportedTask {
+    pub fn count(tasks: &[ImportedTask]) -> usize {
+        tasks.iter().map(|t| 1 + Self::count(&t.subtasks)).sum()
+    }
+}
+
+/// A task from a flat format that refers to its parent by a key.
+pub struct LinkedTask<K> {
+    pub task: ImportedTask,
+    pub key: Option<K>,
+    pub parent: Option<K>,
+}
+
+/// Nests linked tasks under their parents, keeping the input order among
+/// siblings. Tasks whose parent isn't in the input become top level tasks,
+/// and parent cycles are broken by moving one link to the top level.
+pub fn nest<K: Hash + Eq + Clone>(tasks: Vec<LinkedTask<K>>) -> Vec<ImportedTask> {
+    let keys: Vec<K> = tasks.iter().filter_map(|t| t.key.clone()).collect();
+    let mut order: Vec<Option<K>> = Vec::new();
+    let mut by_parent: HashMap<Option<K>, Vec<LinkedTask<K>>> = HashMap::new();
+    for task in tasks {
+        let parent = task.parent.clone().filter(|p| keys.contains(p) && Some(p) != task.key.as_ref());
+        if !by_parent.contains_key(&parent) {
+            order.push(parent.clone());
+        }
+        by_parent.entry(parent).or_default().push(task);
+    }
+
+    fn attach<K: Hash + Eq + Clone>(
+        linked: LinkedTask<K>, by_parent: &mut HashMap<Option<K>, Vec<LinkedTask<K>>>,
+    ) -> ImportedTask {
+        let mut task = linked.task;
+        if let Some(children) = linked.key.and_then(|key| by_parent.remove(&Some(key))) {
+            task.subtasks = children.into_iter().map(|child| attach(child, by_parent)).collect();
+        }
+        task
+    }
+
+    let top_level = by_parent.remove(&None).unwrap_or_default();
+    let mut roots: Vec<ImportedTask> = top_level.into_iter().map(|task| attach(task, &mut by_parent)).collect();
+    // Groups left over after the top level are stuck in a parent cycle
+    for parent in order {
+        if let Some(group) = by_parent.remove(&parent) {
+            roots.extend(group.into_iter().map(|task| attach(task, &mut by_parent)));
+        }
+    }
+    roots
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportMode {
+    /// Adds the imported tasks next to the existing ones, with new ids.
+    Merge,
+    /// Moves the tasks in the list to the trash first. Ids from the file are
+    /// kept when every imported task has a distinct one that isn't taken.
+    Replace,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    TodoTxt,
+    Markdown,
+    ICalendar,
+    Csv,
+    Taskwarrior,
+    Org,
+}
+
+impl Format {
+    pub const ALL: [Format; 7] = [
+        Format::Json, Format::TodoTxt, Format::Markdown, Format::ICalendar, Format::Csv, Format::Taskwarrior,
+        Format::Org,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::TodoTxt => "todotxt",
+            Format::Markdown => "markdown",
+            Format::ICalendar => "ical",
+            Format::Csv => "csv",
+            Format::Taskwarrior => "taskwarrior",
+            Format::Org => "org",
+        }
+    }
+
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Format::Json => &["json"],
+            Format::TodoTxt => &["txt"],
+            Format::Markdown => &["md", "markdown"],
+            Format::ICalendar => &["ics", "ical"],
+            Format::Csv => &["csv"],
+            // Taskwarrior exports are plain .json files
+            Format::Taskwarrior => &[],
+            Format::Org => &["org"],
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self, Box<dyn Error>> {
+        let name = name.trim().to_lowercase();
+        Self::ALL.into_iter()
+            .find(|f| f.name() == name || f.extensions().contains(&name.as_str()))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(|f| f.name()).collect();
+                format!("Unknown format '{}', expected one of: {}", name, names.join(", ")).into()
+            })
+    }
+
+    /// Guesses the format from a file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        Self::ALL.into_iter().find(|f| f.extensions().contains(&extension.as_str()))
+    }
+
+    pub fn export(self, tasks: &[TaskWithKids]) -> Result<String, Box<dyn Error>> {
+        match self {
+            Format::Json => json::export(tasks),
+            Format::TodoTxt => todotxt::export(tasks),
+            Format::Markdown => markdown::export(tasks),
+            Format::ICalendar => ical::export(tasks),
+            Format::Csv => csv::export(tasks),
+            Format::Taskwarrior => taskwarrior::export(tasks),
+            Format::Org => org::export(tasks),
+        }
+    }
+
+    pub fn import(self, input: &str) -> Result<Vec<ImportedTask>, Box<dyn Error>> {
+        match self {
+            Format::Json => json::import(input),
+            Format::TodoTxt => todotxt::import(input),
+            Format::Markdown => markdown::import(input),
+            Format::ICalendar => ical::import(input),
+            Format::Csv => csv::import(input),
+            Format::Taskwarrior => taskwarrior::import(input),
+            Format::Org => org::import(input),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn linked(name: &str, key: u32, parent: Option<u32>) -> LinkedTask<u32> {
+        let task = CreateTask { name: name.to_string(), ..Default::default() };
+        LinkedTask { task: ImportedTask { task, ..Default::default() }, key: Some(key), parent }
+    }
+
+    #[test]
+    fn nesting_linked_tasks() {
+        let tasks = nest(vec![
+            linked("child", 2, Some(1)),
+            linked("root", 1, None),
+            linked("orphan", 3, Some(99)),
+            linked("cycle a", 4, Some(5)),
+            linked("cycle b", 5, Some(4)),
+        ]);
+        let names: Vec<&str> = tasks.iter().map(|t| t.task.name.as_str()).collect();
+        assert_eq!(names, ["root", "orphan", "cycle a"]);
+        assert_eq!(tasks[0].subtasks[0].task.name, "child");
+        assert_eq!(tasks[2].subtasks[0].task.name, "cycle b");
+        assert_eq!(ImportedTask::count(&tasks), 5);
+    }
+}
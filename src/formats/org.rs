@@ -0,0 +1,351 @@
+//! Org-mode outlines, one heading per task:
+//!
+//! ```text
+//! * TODO [#B] Plan launch                                   :work:
+//!   DEADLINE: <2025-02-01 Sat>
+//!   :PROPERTIES:
+//!   :ID:       0b5f…
+//!   :CREATED:  [2025-01-10 Fri 12:00]
+//!   :END:
+//!   Body text is the description.
+//! ** DONE Book venue
+//!    CLOSED: [2025-01-12 Sun 16:20]
+//! ```
+//!
+//! The heading level gives the depth. `DONE` and `CANCELED` headings are
+//! completed, any other heading is an open task. `DEADLINE` is the due date,
+//! or `SCHEDULED` when there is no deadline, and `CLOSED` the completion
+//! time. The `ID` property is the task's uuid, so importing an edited export
+//! again updates the same tasks.
+
+use std::error::Error;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::{formats::ImportedTask, task::*};
+
+
+const TAG_COLUMN: usize = 60;
+
+fn priority_letter(priority: Priority) -> Option<char> {
+    match priority {
+        Priority::None => None,
+        Priority::Low => Some('D'),
+        Priority::Medium => Some('C'),
+        Priority::High => Some('B'),
+        Priority::Urgent => Some('A'),
+    }
+}
+
+fn priority_from_letter(letter: char) -> Option<Priority> {
+    match letter {
+        'A' => Some(Priority::Urgent),
+        'B' => Some(Priority::High),
+        'C' => Some(Priority::Medium),
+        'D'..='Z' => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+fn format_timestamp(date_time: NaiveDateTime, with_time: bool, open: char, close: char) -> String {
+    let format = if with_time { "%Y-%m-%d %a %H:%M" } else { "%Y-%m-%d %a" };
+    format!("{}{}{}", open, date_time.format(format), close)
+}
+
+/// Parses the inside of `<2025-02-01 Sat 09:30 +1w>`, ignoring the weekday
+/// and repeaters.
+fn parse_timestamp(text: &str) -> Result<DueDate, Box<dyn Error>> {
+    let inner = text.trim().trim_start_matches(['<', '[']).trim_end_matches(['>', ']']);
+    let mut parts = inner.split_whitespace();
+    let date = parts.next()
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok())
+        .ok_or(format!("Invalid timestamp '{}'", text))?;
+    let time = parts.find_map(|part| NaiveTime::parse_from_str(part, "%H:%M").ok());
+    Ok(match time {
+        Some(time) => DueDate::DateTime(date.and_time(time)),
+        None => DueDate::Date(date),
+    })
+}
+
+pub fn export(tasks: &[TaskWithKids]) -> Result<String, Box<dyn Error>> {
+    let mut output = String::new();
+    for task in tasks {
+        export_tree(task, 1, &mut output);
+    }
+    Ok(output)
+}
+
+fn export_tree(node: &TaskWithKids, level: usize, output: &mut String) {
+    let task = &node.task;
+    let mut heading = format!("{} {}", "*".repeat(level), if task.completed { "DONE" } else { "TODO" });
+    if let Some(letter) = priority_letter(task.priority) {
+        heading.push_str(&format!(" [#{}]", letter));
+    }
+    heading.push(' ');
+    heading.push_str(&task.name);
+    if !task.tags.is_empty() {
+        let tags = format!(":{}:", task.tags.join(":"));
+        let padding = TAG_COLUMN.saturating_sub(heading.chars().count() + tags.chars().count()).max(1);
+        heading.push_str(&" ".repeat(padding));
+        heading.push_str(&tags);
+    }
+    output.push_str(&heading);
+    output.push('\n');
+
+    let indent = " ".repeat(level + 1);
+    let mut planning = Vec::new();
+    if let Some(completed_at) = task.completed_at.filter(|_| task.completed) {
+        planning.push(format!("CLOSED: {}", format_timestamp(utc_to_local(completed_at), true, '[', ']')));
+    }
+    match task.due_date {
+        Some(DueDate::Date(date)) => planning.push(format!(
+            "DEADLINE: {}", format_timestamp(date.and_time(NaiveTime::MIN), false, '<', '>'))),
+        Some(DueDate::DateTime(date_time)) => planning.push(format!(
+            "DEADLINE: {}", format_timestamp(date_time, true, '<', '>'))),
+        None => {}
+    }
+    if !planning.is_empty() {
+        output.push_str(&format!("{}{}\n", indent, planning.join(" ")));
+    }
+
+    // Times are stored in UTC, Org timestamps are local
+    let created = utc_to_local(task.creation_date);
+    output.push_str(&format!("{}:PROPERTIES:\n", indent));
+    if let Some(uuid) = &task.uuid {
+        output.push_str(&format!("{}:ID:       {}\n", indent, uuid));
+    }
+    output.push_str(&format!("{}:CREATED:  {}\n", indent, format_timestamp(created, true, '[', ']')));
+    if let Some(recurrence) = &task.recurrence {
+        output.push_str(&format!("{}:RRULE:    {}\n", indent, recurrence.to_rrule()));
+    }
+    output.push_str(&format!("{}:END:\n", indent));
+
+    if let Some(description) = &task.description {
+        for line in description.lines() {
+            match line.trim().is_empty() {
+                true => output.push('\n'),
+                false => output.push_str(&format!("{}{}\n", indent, line)),
+            }
+        }
+    }
+
+    for subtask in &node.subtasks {
+        export_tree(subtask, level + 1, output);
+    }
+}
+
+/// A heading whose body and subtasks may still follow.
+struct OpenHeading {
+    level: usize,
+    task: ImportedTask,
+    body: Vec<String>,
+    /// Name of the drawer being read, e.g. `PROPERTIES`
+    drawer: Option<String>,
+}
+
+impl OpenHeading {
+    fn parse(line: &str) -> Option<Self> {
+        let level = line.chars().take_while(|c| *c == '*').count();
+        if level == 0 {
+            return None;
+        }
+        let rest = line[level..].strip_prefix([' ', '\t'])?;
+
+        let mut words: Vec<&str> = rest.split_whitespace().collect();
+        let mut task = CreateTask::default();
+        let mut tags = Vec::new();
+
+        if let Some(last) = words.last()
+            && last.len() > 2 && last.starts_with(':') && last.ends_with(':') {
+            tags = last.trim_matches(':').split(':')
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect();
+            words.pop();
+        }
+        match words.first() {
+            Some(&("DONE" | "CANCELED" | "CANCELLED")) => {
+                task.completed = true;
+                words.remove(0);
+            }
+            Some(&("TODO" | "NEXT" | "WAITING" | "HOLD")) => {
+                words.remove(0);
+            }
+            _ => {}
+        }
+        if let Some(word) = words.first()
+            && let Some(letter) = word.strip_prefix("[#").and_then(|w| w.strip_suffix(']'))
+            && let Some(priority) = letter.chars().next().and_then(priority_from_letter)
+            && letter.len() == 1 {
+            task.priority = priority;
+            words.remove(0);
+        }
+        task.name = words.join(" ");
+
+        Some(Self { level, task: ImportedTask { task, tags, subtasks: Vec::new() }, body: Vec::new(), drawer: None })
+    }
+
+    /// Reads a line below the heading: planning, drawers, or description.
+    fn add_line(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        let text = line.trim();
+        if let Some(drawer) = &self.drawer {
+            if text.eq_ignore_ascii_case(":END:") {
+                self.drawer = None;
+            } else if drawer == "PROPERTIES" {
+                self.add_property(text)?;
+            }
+            return Ok(());
+        }
+
+        if self.body.is_empty()
+            && let Some(name) = text.strip_prefix(':').and_then(|t| t.strip_suffix(':'))
+            && !name.is_empty() && !name.contains(char::is_whitespace) {
+            self.drawer = Some(name.to_uppercase());
+            return Ok(());
+        }
+        if self.body.is_empty() && ["DEADLINE:", "SCHEDULED:", "CLOSED:"].iter().any(|k| text.starts_with(k)) {
+            return self.add_planning(text);
+        }
+        self.body.push(line.to_string());
+        Ok(())
+    }
+
+    fn add_planning(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        let mut scheduled = None;
+        let mut rest = text;
+        while let Some((keyword, after)) = rest.split_once(':') {
+            let end = after.find(['>', ']']).map(|i| i + 1).unwrap_or(after.len());
+            let timestamp = &after[..end];
+            match keyword.trim() {
+                "DEADLINE" => self.task.task.due_date = Some(parse_timestamp(timestamp)?),
+                "SCHEDULED" => scheduled = Some(parse_timestamp(timestamp)?),
+                "CLOSED" => self.task.task.completed_at = Some(local_to_utc(match parse_timestamp(timestamp)? {
+                    DueDate::Date(date) => date.and_time(NaiveTime::MIN),
+                    DueDate::DateTime(date_time) => date_time,
+                })),
+                _ => {}
+            }
+            rest = &after[end..];
+        }
+        if self.task.task.due_date.is_none() {
+            self.task.task.due_date = scheduled;
+        }
+        Ok(())
+    }
+
+    fn add_property(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
+        let Some((name, value)) = text.strip_prefix(':').and_then(|t| t.split_once(':')) else {
+            return Ok(());
+        };
+        let value = value.trim();
+        match name.to_uppercase().as_str() {
+            "ID" if !value.is_empty() => self.task.task.uuid = Some(value.to_string()),
+            "CREATED" => {
+                let created = match parse_timestamp(value)? {
+                    DueDate::Date(date) => date.and_time(NaiveTime::MIN),
+                    DueDate::DateTime(date_time) => date_time,
+                };
+                self.task.task.creation_date = Some(local_to_utc(created));
+            }
+            "RRULE" => self.task.task.recurrence = Some(Recurrence::from_rrule(value)?),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> ImportedTask {
+        while self.body.last().is_some_and(|line| line.trim().is_empty()) {
+            self.body.pop();
+        }
+        let indent = self.body.iter()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.len() - line.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let lines: Vec<&str> = self.body.iter()
+            .map(|line| if line.trim().is_empty() { "" } else { &line[indent..] })
+            .collect();
+        if !lines.is_empty() {
+            self.task.task.description = Some(lines.join("\n"));
+        }
+        self.task
+    }
+}
+
+/// Moves the innermost open heading into its parent, or into `roots` if it's
+/// a top level heading.
+fn close_heading(stack: &mut Vec<OpenHeading>, roots: &mut Vec<ImportedTask>) {
+    if let Some(heading) = stack.pop() {
+        let task = heading.finish();
+        match stack.last_mut() {
+            Some(parent) => parent.task.subtasks.push(task),
+            None => roots.push(task),
+        }
+    }
+}
+
+pub fn import(input: &str) -> Result<Vec<ImportedTask>, Box<dyn Error>> {
+    let mut roots = Vec::new();
+    let mut stack: Vec<OpenHeading> = Vec::new();
+
+    for (number, line) in input.lines().enumerate() {
+        if let Some(heading) = OpenHeading::parse(line) {
+            if heading.task.task.name.is_empty() {
+                return Err(format!("Line {}: Heading without a title", number + 1).into());
+            }
+            while stack.last().is_some_and(|open| open.level >= heading.level) {
+                close_heading(&mut stack, &mut roots);
+            }
+            stack.push(heading);
+        } else if let Some(open) = stack.last_mut() {
+            open.add_line(line).map_err(|e| format!("Line {}: {}", number + 1, e))?;
+        }
+    }
+
+    while !stack.is_empty() {
+        close_heading(&mut stack, &mut roots);
+    }
+    Ok(roots)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn org_outline() -> Result<(), Box<dyn Error>> {
+        let tasks = import("\
+#+TITLE: Plans
+
+* TODO [#A] Release                                            :work:ops:
+  SCHEDULED: <2025-03-01 Sat> DEADLINE: <2025-03-05 Wed 17:00 +1w>
+  :LOGBOOK:
+  - State \"TODO\" from \"NEXT\"
+  :END:
+  Ship it.
+
+    * indented, not a heading
+*** DONE Skipped a level
+    CLOSED: [2025-03-04 Tue 09:15]
+** Plain heading
+SCHEDULED: <2025-03-02 Sun>
+* CANCELED Old idea
+")?;
+        assert_eq!(tasks.len(), 2);
+
+        let release = &tasks[0];
+        assert_eq!(release.task.priority, Priority::Urgent);
+        assert_eq!(release.tags, ["work", "ops"]);
+        assert_eq!(release.task.due_date, Some(DueDate::parse("2025-03-05 17:00")?));
+        assert_eq!(release.task.description.as_deref(), Some("Ship it.\n\n  * indented, not a heading"));
+
+        assert!(release.subtasks[0].task.completed);
+        assert_eq!(release.subtasks[0].task.completed_at,
+            Some(local_to_utc(NaiveDateTime::parse_from_str("2025-03-04 09:15", "%Y-%m-%d %H:%M")?)));
+        assert_eq!(release.subtasks[1].task.name, "Plain heading");
+        assert_eq!(release.subtasks[1].task.due_date, Some(DueDate::parse("2025-03-02")?));
+        assert!(tasks[1].task.completed);
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+//! The JSON of Taskwarrior's `task export` and `task import`.
+//!
+//! Tasks keep their Taskwarrior `uuid`, so importing a newer export updates
+//! the tasks from the previous one. Subtasks are written as `depends` of
+//! their parent, because a parent can't be finished before its subtasks, and
+//! a task another one depends on is imported as its subtask. The description
+//! becomes an annotation and the `project` a `+project` tag. Deleted tasks
+//! are skipped on import.
+//!
+//! Some things are lost on export: Taskwarrior only has three priorities, so
+//! urgent tasks are exported as `H`, and recurrence rules aren't exported
+//! since Taskwarrior repeats tasks through templates of its own.
+
+use std::{collections::HashMap, error::Error};
+
+use chrono::{NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::{formats::{ImportedTask, LinkedTask, nest}, task::*};
+
+
+const DATE_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+#[derive(Serialize, Deserialize)]
+struct TwAnnotation {
+    entry: String,
+    description: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TwTask {
+    uuid: String,
+    status: String,
+    description: String,
+    entry: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due: Option<String>,
+    /// When a completed task was finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    priority: Option<String>,
+    /// Kept as a `+project` tag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    project: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty", deserialize_with = "deserialize_depends")]
+    depends: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<TwAnnotation>,
+}
+
+/// Older Taskwarrior versions write `depends` as one comma separated string.
+fn deserialize_depends<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Depends {
+        List(Vec<String>),
+        Joined(String),
+    }
+    Ok(match Depends::deserialize(deserializer)? {
+        Depends::List(uuids) => uuids,
+        Depends::Joined(uuids) => uuids.split(',').map(|uuid| uuid.trim().to_string()).collect(),
+    })
+}
+
+fn format_utc(date_time: NaiveDateTime) -> String {
+    date_time.format(DATE_FORMAT).to_string()
+}
+
+fn parse_utc(value: &str) -> Result<NaiveDateTime, Box<dyn Error>> {
+    NaiveDateTime::parse_from_str(value, DATE_FORMAT)
+        .map_err(|_| format!("Invalid Taskwarrior date '{}'", value).into())
+}
+
+/// Due dates are local, Taskwarrior's are UTC. A whole-day deadline is local midnight.
+fn due_to_utc(due: DueDate) -> String {
+    format_utc(local_to_utc(match due {
+        DueDate::Date(date) => date.and_time(NaiveTime::MIN),
+        DueDate::DateTime(date_time) => date_time,
+    }))
+}
+
+fn due_from_utc(value: &str) -> Result<DueDate, Box<dyn Error>> {
+    let local = utc_to_local(parse_utc(value)?);
+    Ok(match local.time() == NaiveTime::MIN {
+        true => DueDate::Date(local.date()),
+        false => DueDate::DateTime(local),
+    })
+}
+
+impl TwTask {
+    fn from_tree(node: &TaskWithKids, result: &mut Vec<TwTask>) -> Result<(), Box<dyn Error>> {
+        let task = &node.task;
+        let uuid = task.uuid.clone().ok_or(format!("Task {} has no uuid", task.id))?;
+        let entry = format_utc(task.creation_date);
+
+        let mut depends = Vec::new();
+        for subtask in &node.subtasks {
+            depends.push(subtask.task.uuid.clone().ok_or(format!("Task {} has no uuid", subtask.task.id))?);
+        }
+
+        let project = task.tags.iter().find(|tag| tag.starts_with('+') && tag.len() > 1);
+
+        result.push(TwTask {
+            uuid,
+            status: String::from(if task.completed { "completed" } else { "pending" }),
+            description: task.name.clone(),
+            entry: entry.clone(),
+            due: task.due_date.map(due_to_utc),
+            end: task.completed_at.filter(|_| task.completed).map(format_utc),
+            priority: match task.priority {
+                Priority::None => None,
+                Priority::Low => Some(String::from("L")),
+                Priority::Medium => Some(String::from("M")),
+                Priority::High | Priority::Urgent => Some(String::from("H")),
+            },
+            project: project.map(|tag| tag[1..].to_string()),
+            tags: task.tags.iter().filter(|&tag| Some(tag) != project).cloned().collect(),
+            depends,
+            annotations: task.description.iter()
+                .map(|description| TwAnnotation { entry: entry.clone(), description: description.clone() })
+                .collect(),
+        });
+
+        for subtask in &node.subtasks {
+            Self::from_tree(subtask, result)?;
+        }
+        Ok(())
+    }
+
+    fn into_imported(self) -> Result<ImportedTask, Box<dyn Error>> {
+        if self.description.trim().is_empty() {
+            return Err(format!("Task {} has no description", self.uuid).into());
+        }
+        let annotations: Vec<String> = self.annotations.into_iter().map(|a| a.description).collect();
+        let mut tags = self.tags.iter()
+            .map(|tag| Tag::normalize(tag))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(project) = self.project.as_deref().filter(|project| !project.trim().is_empty()) {
+            tags.push(Tag::normalize(&format!("+{}", project.trim().replace(' ', "-")))?);
+        }
+
+        Ok(ImportedTask {
+            task: CreateTask {
+                uuid: Some(self.uuid),
+                name: self.description,
+                description: (!annotations.is_empty()).then(|| annotations.join("\n")),
+                completed: self.status == "completed",
+                creation_date: Some(parse_utc(&self.entry)?),
+                due_date: self.due.as_deref().map(due_from_utc).transpose()?,
+                completed_at: self.end.as_deref().filter(|_| self.status == "completed")
+                    .map(parse_utc).transpose()?,
+                priority: match self.priority.as_deref() {
+                    None | Some("") => Priority::None,
+                    Some("L") => Priority::Low,
+                    Some("M") => Priority::Medium,
+                    Some("H") => Priority::High,
+                    Some(other) => return Err(format!("Invalid Taskwarrior priority '{}'", other).into()),
+                },
+                ..Default::default()
+            },
+            tags,
+            subtasks: Vec::new(),
+        })
+    }
+}
+
+pub fn export(tasks: &[TaskWithKids]) -> Result<String, Box<dyn Error>> {
+    let mut flat = Vec::new();
+    for task in tasks {
+        TwTask::from_tree(task, &mut flat)?;
+    }
+
+    // One task per line, like `task export`
+    let lines = flat.iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(format!("[\n{}\n]\n", lines.join(",\n")))
+}
+
+pub fn import(input: &str) -> Result<Vec<ImportedTask>, Box<dyn Error>> {
+    let tasks: Vec<TwTask> = serde_json::from_str(input)?;
+    let tasks: Vec<TwTask> = tasks.into_iter().filter(|t| t.status != "deleted").collect();
+
+    // A task is nested under the first task that depends on it
+    let mut parents: HashMap<String, String> = HashMap::new();
+    for task in &tasks {
+        for dependency in &task.depends {
+            parents.entry(dependency.clone()).or_insert_with(|| task.uuid.clone());
+        }
+    }
+
+    let mut linked = Vec::new();
+    for task in tasks {
+        let uuid = task.uuid.clone();
+        let parent = parents.get(&uuid).cloned();
+        let task = task.into_imported()?;
+        linked.push(LinkedTask { task, key: Some(uuid), parent });
+    }
+    Ok(nest(linked))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn taskwarrior_export() -> Result<(), Box<dyn Error>> {
+        let tasks = import(r#"[
+{"id":1,"description":"Release","entry":"20250110T090000Z","modified":"20250110T090000Z","priority":"H","project":"work","status":"pending","tags":["work"],"uuid":"a","depends":["b","c"],"urgency":8.1},
+{"id":0,"description":"Changelog","end":"20250111T090000Z","entry":"20250110T090000Z","status":"completed","uuid":"b","annotations":[{"entry":"20250110T090000Z","description":"see PR"},{"entry":"20250110T100000Z","description":"merged"}]},
+{"id":2,"description":"Tag","entry":"20250110T090000Z","status":"pending","uuid":"c","depends":"d"},
+{"id":3,"description":"Gone","entry":"20250110T090000Z","status":"deleted","uuid":"d"}
+]"#)?;
+        assert_eq!(tasks.len(), 1);
+        let release = &tasks[0];
+        assert_eq!(release.task.uuid.as_deref(), Some("a"));
+        assert_eq!(release.task.priority, Priority::High);
+        assert_eq!(release.tags, ["work", "+work"]);
+
+        let changelog = &release.subtasks[0];
+        assert!(changelog.task.completed);
+        assert_eq!(changelog.task.completed_at, Some(parse_utc("20250111T090000Z")?));
+        assert_eq!(changelog.task.description.as_deref(), Some("see PR\nmerged"));
+        assert_eq!(release.subtasks[1].task.name, "Tag");
+        assert!(release.subtasks[1].subtasks.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn due_dates_are_utc() -> Result<(), Box<dyn Error>> {
+        for due in ["2025-03-01", "2025-03-01 14:30"] {
+            let due = DueDate::parse(due)?;
+            assert_eq!(due_from_utc(&due_to_utc(due))?, due);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,336 @@
+//! The [todo.txt](https://github.com/todotxt/todo.txt) format, one task per line:
+//!
+//! ```text
+//! (B) 2025-01-10 Plan launch +launch @work due:2025-02-01 id:1
+//! x 2025-01-12 2025-01-10 Book venue id:2 parent:1
+//! ```
+//!
+//! Besides the standard `x`, `(A)`, dates, `+project` and `@context` parts, the
+//! hierarchy and the fields todo.txt has no syntax for are stored as `key:value`
+//! extensions: `id:`, `parent:`, `due:`, `rec:`, `pri:` (priority of a completed
+//! task), `tag:` (tags without a `+`/`@` sigil) and `desc:` (percent-encoded).
+//!
+//! Exported tags always follow the name. Words of the name that would otherwise
+//! be read back as a tag or an extension are escaped with a leading `\`, so
+//! `Email \@bob` is a task named `Email @bob` without tags.
+
+use std::error::Error;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{formats::{ImportedTask, LinkedTask, nest}, task::*};
+
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+fn priority_letter(priority: Priority) -> Option<char> {
+    match priority {
+        Priority::None => None,
+        Priority::Low => Some('D'),
+        Priority::Medium => Some('C'),
+        Priority::High => Some('B'),
+        Priority::Urgent => Some('A'),
+    }
+}
+
+fn priority_from_letter(letter: char) -> Option<Priority> {
+    match letter {
+        'A' => Some(Priority::Urgent),
+        'B' => Some(Priority::High),
+        'C' => Some(Priority::Medium),
+        'D'..='Z' => Some(Priority::Low),
+        _ => None,
+    }
+}
+
+const EXTENSION_KEYS: [&str; 7] = ["id", "parent", "due", "rec", "desc", "tag", "pri"];
+
+fn is_project_or_context(word: &str) -> bool {
+    (word.starts_with('+') || word.starts_with('@')) && word.len() > 1
+}
+
+fn escape_name_word(word: &str) -> String {
+    let is_extension = word.split_once(':').is_some_and(|(key, value)| EXTENSION_KEYS.contains(&key) && !value.is_empty());
+    if is_project_or_context(word) || is_extension || word.starts_with('\\') {
+        format!("\\{}", word)
+    } else {
+        word.to_string()
+    }
+}
+
+fn encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for c in text.chars() {
+        match c {
+            '%' => encoded.push_str("%25"),
+            ' ' => encoded.push_str("%20"),
+            '\n' => encoded.push_str("%0A"),
+            '\r' => encoded.push_str("%0D"),
+            '\t' => encoded.push_str("%09"),
+            c => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+fn decode(text: &str) -> Result<String, Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' && tail.len() >= 2 {
+            let hex = std::str::from_utf8(&tail[..2])?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| format!("Invalid escape '%{}'", hex))?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Ok(String::from_utf8(bytes)?)
+}
+
+pub fn export(tasks: &[TaskWithKids]) -> Result<String, Box<dyn Error>> {
+    let mut output = String::new();
+    for task in tasks {
+        export_tree(task, &mut output);
+    }
+    Ok(output)
+}
+
+fn export_tree(node: &TaskWithKids, output: &mut String) {
+    output.push_str(&export_line(&node.task));
+    output.push('\n');
+    for subtask in &node.subtasks {
+        export_tree(subtask, output);
+    }
+}
+
+fn export_line(task: &Task) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let created = utc_to_local(task.creation_date).format(DATE_FORMAT).to_string();
+    let letter = priority_letter(task.priority);
+
+    if task.completed {
+        // todo.txt needs a completion date before the creation date; tasks
+        // completed before it was tracked use the creation date
+        let completed = task.completed_at
+            .map(|date| utc_to_local(date).format(DATE_FORMAT).to_string())
+            .unwrap_or(created.clone());
+        parts.extend([String::from("x"), completed, created]);
+    } else {
+        if let Some(letter) = letter {
+            parts.push(format!("({})", letter));
+        }
+        parts.push(created);
+    }
+
+    parts.push(task.name.split(' ').map(escape_name_word).collect::<Vec<String>>().join(" "));
+
+    for tag in &task.tags {
+        if is_project_or_context(tag) {
+            parts.push(tag.clone());
+        } else {
+            parts.push(format!("tag:{}", tag));
+        }
+    }
+
+    if let (true, Some(letter)) = (task.completed, letter) {
+        parts.push(format!("pri:{}", letter));
+    }
+    if let Some(due) = task.due_date {
+        parts.push(format!("due:{}", due.to_db_string().replace(' ', "T")));
+    }
+    if let Some(recurrence) = &task.recurrence {
+        parts.push(format!("rec:{}", recurrence.to_rrule()));
+    }
+    if let Some(description) = &task.description {
+        parts.push(format!("desc:{}", encode(description)));
+    }
+    parts.push(format!("id:{}", task.id));
+    if let Some(parent_id) = task.parent_id {
+        parts.push(format!("parent:{}", parent_id));
+    }
+
+    parts.join(" ")
+}
+
+pub fn import(input: &str) -> Result<Vec<ImportedTask>, Box<dyn Error>> {
+    let mut lines = Vec::new();
+    for (number, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed = parse_line(line).map_err(|e| format!("Line {}: {}", number + 1, e))?;
+        lines.push(parsed);
+    }
+    Ok(nest(lines))
+}
+
+fn take_date<'a>(words: &mut &'a [&'a str]) -> Option<NaiveDate> {
+    let date = NaiveDate::parse_from_str(words.first()?, DATE_FORMAT).ok()?;
+    *words = &words[1..];
+    Some(date)
+}
+
+/// The UTC time of the start of a local day.
+fn local_midnight(date: NaiveDate) -> NaiveDateTime {
+    local_to_utc(date.and_hms_opt(0, 0, 0).unwrap())
+}
+
+fn parse_line(line: &str) -> Result<LinkedTask<u32>, Box<dyn Error>> {
+    let all_words: Vec<&str> = line.split_whitespace().collect();
+    let mut words = all_words.as_slice();
+    let mut task = CreateTask::default();
+
+    if words.first() == Some(&"x") {
+        task.completed = true;
+        words = &words[1..];
+        let completion = take_date(&mut words);
+        let creation = take_date(&mut words);
+        // A lone date after `x` is the completion date. Both dates are local.
+        task.completed_at = completion.map(local_midnight);
+        if let (Some(_), Some(creation)) = (completion, creation) {
+            task.creation_date = Some(local_midnight(creation));
+        }
+    } else {
+        if let Some(word) = words.first()
+            && let [b'(', letter, b')'] = word.as_bytes()
+            && let Some(priority) = priority_from_letter(*letter as char) {
+            task.priority = priority;
+            words = &words[1..];
+        }
+        if let Some(creation) = take_date(&mut words) {
+            task.creation_date = Some(local_midnight(creation));
+        }
+    }
+
+    let mut id = None;
+    let mut parent = None;
+    let mut tags: Vec<String> = Vec::new();
+    // Name words with a flag telling whether they were escaped
+    let mut name_words: Vec<(&str, bool)> = Vec::new();
+
+    for word in words {
+        if let Some(escaped) = word.strip_prefix('\\') && !escaped.is_empty() {
+            name_words.push((escaped, true));
+            continue;
+        }
+        let (key, value) = word.split_once(':').unwrap_or(("", ""));
+        match key {
+            _ if value.is_empty() => name_words.push((word, false)),
+            "id" => id = Some(value.parse().map_err(|_| format!("Invalid id '{}'", value))?),
+            "parent" => parent = Some(value.parse().map_err(|_| format!("Invalid parent '{}'", value))?),
+            "due" => task.due_date = Some(DueDate::parse(&value.replace('T', " "))?),
+            "rec" => task.recurrence = Some(Recurrence::from_rrule(value)?),
+            "desc" => task.description = Some(decode(value)?),
+            "tag" => tags.push(Tag::normalize(value)?),
+            "pri" => {
+                let mut letters = value.chars();
+                task.priority = match (letters.next(), letters.next()) {
+                    (Some(letter), None) => priority_from_letter(letter),
+                    _ => None,
+                }.ok_or(format!("Invalid priority '{}'", value))?;
+            }
+            _ => name_words.push((word, false)),
+        }
+    }
+
+    // Projects and contexts become tags. Trailing ones are only tags, the ones
+    // inside the sentence stay part of the name too.
+    while let Some(&(word, false)) = name_words.last() && is_project_or_context(word) {
+        tags.insert(0, word.to_string());
+        name_words.pop();
+    }
+    for &(word, escaped) in &name_words {
+        if !escaped && is_project_or_context(word) {
+            tags.push(word.to_string());
+        }
+    }
+    let mut unique_tags: Vec<String> = Vec::new();
+    for tag in tags {
+        if !unique_tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            unique_tags.push(tag);
+        }
+    }
+
+    task.name = name_words.iter().map(|&(word, _)| word).collect::<Vec<&str>>().join(" ");
+    if task.name.is_empty() {
+        return Err("Task has no text".into());
+    }
+    task.id = id;
+
+    Ok(LinkedTask { task: ImportedTask { task, tags: unique_tags, subtasks: Vec::new() }, key: id, parent })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_lines() -> Result<(), Box<dyn Error>> {
+        let tasks = import("\
+(A) 2025-01-10 Call @mom about the +party +family due:2025-01-20 id:1
+x 2025-01-12 2025-01-11 Buy cake pri:B parent:1 id:2
+x 2025-01-12 Done without creation date
+
+Visit https://example.com later
+")?;
+        assert_eq!(tasks.len(), 3);
+
+        let call = &tasks[0];
+        assert_eq!(call.task.name, "Call @mom about the");
+        assert_eq!(call.task.priority, Priority::Urgent);
+        assert_eq!(call.tags, ["+party", "+family", "@mom"]);
+        assert_eq!(call.task.due_date, Some(DueDate::parse("2025-01-20")?));
+
+        let cake = &call.subtasks[0];
+        assert!(cake.task.completed);
+        assert_eq!(cake.task.priority, Priority::High);
+        assert_eq!(cake.task.creation_date.map(|d| utc_to_local(d).date()), NaiveDate::from_ymd_opt(2025, 1, 11));
+        assert_eq!(cake.task.completed_at.map(|d| utc_to_local(d).date()), NaiveDate::from_ymd_opt(2025, 1, 12));
+
+        assert_eq!(tasks[1].task.creation_date, None);
+        assert_eq!(tasks[2].task.name, "Visit https://example.com later");
+        Ok(())
+    }
+
+    #[test]
+    fn descriptions_are_escaped() -> Result<(), Box<dyn Error>> {
+        let description = "50% done\nnext: review";
+        assert_eq!(decode(&encode(description))?, description);
+        assert!(!encode(description).contains(' '));
+        Ok(())
+    }
+
+    #[test]
+    fn sigil_words_stay_in_names() -> Result<(), Box<dyn Error>> {
+        let task = |id, name: &str, tags: &[&str]| TaskWithKids {
+            task: Task {
+                id,
+                name: name.to_string(),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                ..Default::default()
+            },
+            subtasks: Vec::new(),
+        };
+        let exported = export(&[
+            task(1, "Plan +launch", &["+launch"]),
+            task(2, "Email @bob", &[]),
+            task(3, "Read due:friday \\notes", &[]),
+        ])?;
+        assert!(exported.contains("Email \\@bob id:2"));
+
+        let tasks = import(&exported)?;
+        let names: Vec<&str> = tasks.iter().map(|t| t.task.name.as_str()).collect();
+        assert_eq!(names, ["Plan +launch", "Email @bob", "Read due:friday \\notes"]);
+        assert_eq!(tasks[0].tags, ["+launch"]);
+        assert!(tasks[1].tags.is_empty() && tasks[2].tags.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn errors_report_line_numbers() {
+        let error = import("ok\n(A) id:x broken").unwrap_err();
+        assert!(error.to_string().starts_with("Line 2"));
+    }
+}
@@ -0,0 +1,119 @@
+//! The undo history. Each undoable step stores the rows of the tasks it
+//! touched as they were before and after, so undoing or redoing a step only
+//! has to write one of the two states back, whatever the operation was. Most
+//! steps only compare the subtrees of the tasks they work on and the tasks
+//! they create, so moving a task to the trash stores the whole subtree it
+//! took along.
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+
+/// A task row with its tags.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskSnapshot {
+    pub id: u32,
+    pub parent_id: Option<u32>,
+    /// The other columns by name, so columns added later are kept as well
+    pub columns: Map<String, Value>,
+    pub tags: Vec<String>,
+}
+
+/// The state of one task before and after a step. `None` means the task
+/// didn't exist.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TaskChange {
+    pub before: Option<TaskSnapshot>,
+    pub after: Option<TaskSnapshot>,
+}
+
+impl TaskChange {
+    /// The change that reverts this one.
+    pub fn inverse(&self) -> Self {
+        Self { before: self.after.clone(), after: self.before.clone() }
+    }
+}
+
+/// One undoable step, e.g. deleting a task.
+#[derive(Debug, Clone)]
+pub struct HistoryStep {
+    pub id: u32,
+    pub label: String,
+    pub changes: Vec<TaskChange>,
+}
+
+/// The changes that turn the `before` rows into the `after` rows, by task id.
+pub fn diff(before: Vec<TaskSnapshot>, after: Vec<TaskSnapshot>) -> Vec<TaskChange> {
+    let mut states: BTreeMap<u32, TaskChange> = BTreeMap::new();
+    for task in before {
+        states.insert(task.id, TaskChange { before: Some(task), after: None });
+    }
+    for task in after {
+        let change = states.entry(task.id).or_insert(TaskChange { before: None, after: None });
+        change.after = Some(task);
+    }
+    states.into_values().filter(|change| change.before != change.after).collect()
+}
+
+/// Removes the purged tasks from a step, so undoing or redoing it can't bring
+/// them back. Returns `None` when nothing is left of the step, or when it
+/// would put another task under a purged one and has to go as a whole.
+pub fn forget(changes: Vec<TaskChange>, purged: &HashSet<u32>) -> Option<Vec<TaskChange>> {
+    let mut kept = Vec::new();
+    for change in changes {
+        let snapshots = || change.before.iter().chain(change.after.iter());
+        if snapshots().any(|task| purged.contains(&task.id)) {
+            continue;
+        }
+        if snapshots().any(|task| task.parent_id.is_some_and(|id| purged.contains(&id))) {
+            return None;
+        }
+        kept.push(change);
+    }
+    (!kept.is_empty()).then_some(kept)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(id: u32, parent_id: Option<u32>, name: &str) -> TaskSnapshot {
+        let mut columns = Map::new();
+        columns.insert(String::from("name"), Value::from(name));
+        TaskSnapshot { id, parent_id, columns, tags: Vec::new() }
+    }
+
+    #[test]
+    fn diff_keeps_only_changed_tasks() {
+        let before = vec![snapshot(1, None, "kept"), snapshot(2, None, "renamed"), snapshot(3, Some(2), "deleted")];
+        let after = vec![snapshot(1, None, "kept"), snapshot(2, None, "new name"), snapshot(4, None, "created")];
+
+        let changes = diff(before, after);
+        let ids: Vec<(Option<u32>, Option<u32>)> = changes.iter()
+            .map(|c| (c.before.as_ref().map(|t| t.id), c.after.as_ref().map(|t| t.id)))
+            .collect();
+        assert_eq!(ids, [(Some(2), Some(2)), (Some(3), None), (None, Some(4))]);
+
+        let undo = changes[1].inverse();
+        assert_eq!(undo.after, Some(snapshot(3, Some(2), "deleted")));
+        assert_eq!(undo.before, None);
+    }
+
+    #[test]
+    fn forgetting_purged_tasks() {
+        let change = |before: Option<TaskSnapshot>, after: Option<TaskSnapshot>| TaskChange { before, after };
+        let purged = HashSet::from([2, 3]);
+
+        let rename = change(Some(snapshot(1, None, "a")), Some(snapshot(1, None, "b")));
+        let delete = change(Some(snapshot(3, Some(2), "deleted")), None);
+        assert_eq!(forget(vec![rename.clone(), delete.clone()], &purged), Some(vec![rename]));
+        assert_eq!(forget(vec![delete], &purged), None);
+
+        // Undoing the move would need the purged parent
+        let moved = change(Some(snapshot(4, Some(2), "moved")), Some(snapshot(4, None, "moved")));
+        assert_eq!(forget(vec![moved], &purged), None);
+    }
+}
@@ -1,7 +1,12 @@
 pub mod task;
 pub mod config;
+pub mod cli;
 pub mod todotui;
 pub mod database;
+pub mod formats;
+pub mod history;
+pub mod migration;
+pub mod query;
 pub mod todolist;
 
 pub mod ui;
@@ -1,14 +1,21 @@
-use std::{error::Error};
+use std::{env, process::ExitCode};
 
-use dotenv;
+use todolist::{cli, todotui::TodoTUI};
 
-use todolist::todotui::TodoTUI;
-
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> ExitCode {
     dotenv::dotenv().ok();
 
-    let mut tui = TodoTUI::new()?;
-    tui.run()?;
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args);
+    }
 
-    Ok(())
+    let result = TodoTUI::new().and_then(|mut tui| tui.run());
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,300 @@
+use std::error::Error;
+
+use rusqlite::{Connection, TransactionBehavior};
+
+
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+// Append-only: never edit or reorder a migration once it has been released,
+// add a new one with the next version instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create Task table",
+        sql: "CREATE TABLE IF NOT EXISTS Task (
+                id            INTEGER PRIMARY KEY AUTOINCREMENT,
+                parent_id     INTEGER DEFAULT NULL,
+                name          TEXT NOT NULL,
+                completed     INTEGER DEFAULT 0,
+                description   TEXT,
+                creation_date TEXT DEFAULT CURRENT_TIMESTAMP,
+
+                FOREIGN KEY (parent_id) REFERENCES Task(id) ON DELETE CASCADE
+            );",
+    },
+    Migration {
+        version: 2,
+        description: "add Task.due_date",
+        sql: "ALTER TABLE Task ADD COLUMN due_date TEXT DEFAULT NULL;",
+    },
+    Migration {
+        version: 3,
+        description: "add Task.priority",
+        sql: "ALTER TABLE Task ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 4,
+        description: "create Tag and TaskTag tables",
+        sql: "CREATE TABLE Tag (
+                id   INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE
+            );
+            CREATE TABLE TaskTag (
+                task_id INTEGER NOT NULL,
+                tag_id  INTEGER NOT NULL,
+
+                PRIMARY KEY (task_id, tag_id),
+                FOREIGN KEY (task_id) REFERENCES Task(id) ON DELETE CASCADE,
+                FOREIGN KEY (tag_id) REFERENCES Tag(id) ON DELETE CASCADE
+            );",
+    },
+    Migration {
+        version: 5,
+        description: "add Task.recurrence",
+        sql: "ALTER TABLE Task ADD COLUMN recurrence TEXT DEFAULT NULL;",
+    },
+    Migration {
+        version: 6,
+        description: "add Task.position for manual sibling ordering",
+        sql: "ALTER TABLE Task ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+            UPDATE Task SET position = id;
+            CREATE INDEX idx_task_parent_position ON Task (parent_id, position);",
+    },
+    Migration {
+        version: 7,
+        description: "add Task.collapsed",
+        sql: "ALTER TABLE Task ADD COLUMN collapsed INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 8,
+        description: "add Task.uuid to match tasks across imports",
+        sql: "ALTER TABLE Task ADD COLUMN uuid TEXT DEFAULT NULL;
+            CREATE UNIQUE INDEX idx_task_uuid ON Task (uuid);",
+    },
+    Migration {
+        version: 9,
+        description: "create TaskSearch full-text index",
+        sql: "CREATE VIRTUAL TABLE TaskSearch USING fts5(
+                name, description,
+                content = 'Task', content_rowid = 'id', tokenize = 'unicode61 remove_diacritics 2'
+            );
+            INSERT INTO TaskSearch (TaskSearch) VALUES ('rebuild');
+
+            CREATE TRIGGER task_search_insert AFTER INSERT ON Task BEGIN
+                INSERT INTO TaskSearch (rowid, name, description) VALUES (new.id, new.name, new.description);
+            END;
+            CREATE TRIGGER task_search_delete AFTER DELETE ON Task BEGIN
+                INSERT INTO TaskSearch (TaskSearch, rowid, name, description)
+                    VALUES ('delete', old.id, old.name, old.description);
+            END;
+            CREATE TRIGGER task_search_update AFTER UPDATE OF name, description ON Task BEGIN
+                INSERT INTO TaskSearch (TaskSearch, rowid, name, description)
+                    VALUES ('delete', old.id, old.name, old.description);
+                INSERT INTO TaskSearch (rowid, name, description) VALUES (new.id, new.name, new.description);
+            END;",
+    },
+    Migration {
+        version: 10,
+        description: "create UndoHistory table",
+        sql: "CREATE TABLE UndoHistory (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                label      TEXT NOT NULL,
+                changes    TEXT NOT NULL,
+                undone     INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT DEFAULT CURRENT_TIMESTAMP
+            );",
+    },
+    Migration {
+        version: 11,
+        description: "add Task.deleted_at for the trash",
+        sql: "ALTER TABLE Task ADD COLUMN deleted_at TEXT DEFAULT NULL;
+            CREATE INDEX idx_task_deleted_at ON Task (deleted_at);",
+    },
+    Migration {
+        version: 12,
+        description: "add Task.archived_at",
+        sql: "ALTER TABLE Task ADD COLUMN archived_at TEXT DEFAULT NULL;
+            CREATE INDEX idx_task_archived_at ON Task (archived_at);",
+    },
+    Migration {
+        version: 13,
+        description: "create TaskEvent audit table",
+        sql: "CREATE TABLE TaskEvent (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id    INTEGER NOT NULL,
+                kind       TEXT NOT NULL,
+                field      TEXT,
+                old_value  TEXT,
+                new_value  TEXT,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+
+                FOREIGN KEY (task_id) REFERENCES Task(id) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_task_event_task_id ON TaskEvent (task_id);",
+    },
+    Migration {
+        version: 14,
+        description: "add Task.completed_at",
+        // Tasks completed before this version get the time of their last
+        // completion event, if there is one. Events are in UTC, completed_at
+        // was local until version 16
+        sql: "ALTER TABLE Task ADD COLUMN completed_at TEXT DEFAULT NULL;
+            UPDATE Task SET completed_at = (
+                SELECT datetime(MAX(created_at), 'localtime') FROM TaskEvent WHERE TaskEvent.task_id = Task.id AND kind = 'complete'
+            ) WHERE completed = 1;",
+    },
+    Migration {
+        version: 15,
+        description: "assign a uuid to every task",
+        // Random version 4 uuids, also for rows written without one later on,
+        // e.g. by undoing a change recorded before this version
+        sql: "UPDATE Task SET uuid = lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+                substr(hex(randomblob(2)), 2) || '-' || substr('89AB', abs(random()) % 4 + 1, 1) ||
+                substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6)))
+             WHERE uuid IS NULL;
+            CREATE TRIGGER task_uuid_insert AFTER INSERT ON Task WHEN new.uuid IS NULL BEGIN
+                UPDATE Task SET uuid = lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+                    substr(hex(randomblob(2)), 2) || '-' || substr('89AB', abs(random()) % 4 + 1, 1) ||
+                    substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6)))
+                WHERE id = new.id;
+            END;
+            CREATE TRIGGER task_uuid_update AFTER UPDATE OF uuid ON Task WHEN new.uuid IS NULL BEGIN
+                UPDATE Task SET uuid = lower(hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+                    substr(hex(randomblob(2)), 2) || '-' || substr('89AB', abs(random()) % 4 + 1, 1) ||
+                    substr(hex(randomblob(2)), 2) || '-' || hex(randomblob(6)))
+                WHERE id = new.id;
+            END;",
+    },
+    Migration {
+        version: 16,
+        description: "store trash, archive and completion times in UTC",
+        // Like creation_date and TaskEvent.created_at
+        sql: "UPDATE Task SET deleted_at = datetime(deleted_at, 'utc'), archived_at = datetime(archived_at, 'utc'),
+                completed_at = datetime(completed_at, 'utc');",
+    },
+];
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(connection: &Connection) -> Result<u32, Box<dyn Error>> {
+    let version = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    Ok(version)
+}
+
+/// Brings the schema up to `latest_version()`.
+///
+/// Every migration runs in its own immediate transaction together with the
+/// `user_version` bump, so a failed migration leaves the database at the
+/// previous version and concurrent openers never apply the same step twice.
+pub fn run(connection: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let current = current_version(connection)?;
+    let latest = latest_version();
+    if current > latest {
+        return Err(format!(
+            "Database schema version {} is newer than this build supports ({}). \
+             Please upgrade todolist.", current, latest
+        ).into());
+    }
+
+    for migration in MIGRATIONS {
+        let tx = connection.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        if current_version(&tx)? >= migration.version {
+            continue;
+        }
+        tx.execute_batch(migration.sql)
+            .map_err(|e| format!(
+                "Migration {} ({}) failed: {}", migration.version, migration.description, e
+            ))?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_are_sequential() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as u32 + 1);
+        }
+    }
+
+    #[test]
+    fn fresh_database_is_migrated() -> Result<(), Box<dyn Error>> {
+        let mut connection = Connection::open_in_memory()?;
+        run(&mut connection)?;
+        assert_eq!(current_version(&connection)?, latest_version());
+
+        // Running again is a no-op
+        run(&mut connection)?;
+        assert_eq!(current_version(&connection)?, latest_version());
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_database_keeps_data() -> Result<(), Box<dyn Error>> {
+        let mut connection = Connection::open_in_memory()?;
+        connection.execute_batch(MIGRATIONS[0].sql)?;
+        connection.execute("INSERT INTO Task (name) VALUES ('Old task')", ())?;
+
+        run(&mut connection)?;
+
+        let name: String = connection.query_row("SELECT name FROM Task", [], |row| row.get(0))?;
+        assert_eq!(name, "Old task");
+
+        // Old tasks get a uuid, and so do rows written without one
+        let uuid = |connection: &Connection| -> Result<uuid::Uuid, Box<dyn Error>> {
+            let uuid: String = connection.query_row("SELECT uuid FROM Task", [], |row| row.get(0))?;
+            Ok(uuid::Uuid::parse_str(&uuid)?)
+        };
+        assert_eq!(uuid(&connection)?.get_version_num(), 4);
+        connection.execute("UPDATE Task SET uuid = NULL", ())?;
+        assert_eq!(uuid(&connection)?.get_version_num(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn local_times_become_utc() -> Result<(), Box<dyn Error>> {
+        let mut connection = Connection::open_in_memory()?;
+        for migration in &MIGRATIONS[..13] {
+            connection.execute_batch(migration.sql)?;
+        }
+        connection.pragma_update(None, "user_version", 13)?;
+        connection.execute(
+            "INSERT INTO Task (name, completed, deleted_at) VALUES ('Old task', 1, '2025-01-10 12:00:00')", ())?;
+        connection.execute(
+            "INSERT INTO TaskEvent (task_id, kind, created_at) VALUES (1, 'complete', '2025-01-09 08:00:00')", ())?;
+
+        run(&mut connection)?;
+
+        let expected: String = connection.query_row("SELECT datetime('2025-01-10 12:00:00', 'utc')", [], |row| row.get(0))?;
+        let (deleted_at, completed_at, created_at): (String, String, String) = connection.query_row(
+            "SELECT deleted_at, completed_at, TaskEvent.created_at FROM Task JOIN TaskEvent ON TaskEvent.task_id = Task.id",
+            [], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        assert_eq!(deleted_at, expected);
+        // Event times were UTC all along
+        assert_eq!(completed_at, "2025-01-09 08:00:00");
+        assert_eq!(created_at, "2025-01-09 08:00:00");
+        Ok(())
+    }
+
+    #[test]
+    fn newer_database_is_refused() -> Result<(), Box<dyn Error>> {
+        let mut connection = Connection::open_in_memory()?;
+        connection.pragma_update(None, "user_version", latest_version() + 1)?;
+
+        assert!(run(&mut connection).is_err());
+        Ok(())
+    }
+}
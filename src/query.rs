@@ -0,0 +1,489 @@
+//! The filter language of task views, e.g.
+//!
+//! ```text
+//! status:open tag:work due<7d depth<=2 "deploy"
+//! ```
+//!
+//! Terms are joined with AND unless separated by `OR`, `-` negates a term and
+//! parentheses group. Supported terms:
+//!
+//! - `status:open`, `status:done`, `status:all`
+//! - `tag:NAME`, which also matches the tag with a sigil, e.g. `@NAME`
+//! - `priority:high`, `priority>=medium` (or `pri`)
+//! - `due<7d`, `due>=2025-03-01`, `due:today`, `due:none`, `due:any`, `due:overdue`;
+//!   relative dates count days (`d`) or weeks (`w`) from today
+//! - `completed>7d`, `completed:yesterday`, `completed<2025-01-01` (or `closed`)
+//!   compare completion dates; here `7d` counts back, so `completed>7d` is
+//!   anything finished in the last week
+//! - `depth<=2`, where top level tasks have depth 0
+//! - plain words and `"quoted phrases"`, searched in names and descriptions;
+//!   words like `re:deploy` or URLs whose prefix isn't one of the keys above
+//!   are plain words too
+//!
+//! A [`Query`] compiles to one SQL statement selecting the matching tasks.
+
+use std::error::Error;
+
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+
+use crate::task::{Priority, SearchHit};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn sql(self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+/// A date in a query, resolved against the current date when compiled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueryDate {
+    Absolute(NaiveDate),
+    /// Days from today, negative for the past
+    Relative(i64),
+}
+
+impl QueryDate {
+    fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+        match input {
+            "today" => return Ok(QueryDate::Relative(0)),
+            "tomorrow" => return Ok(QueryDate::Relative(1)),
+            "yesterday" => return Ok(QueryDate::Relative(-1)),
+            _ => {}
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+            return Ok(QueryDate::Absolute(date));
+        }
+        let invalid = || format!("Invalid date '{}', expected YYYY-MM-DD, today or a count like 7d or 2w", input);
+        let (count, days) = match input.char_indices().last() {
+            Some((i, 'd')) => (&input[..i], 1),
+            Some((i, 'w')) => (&input[..i], 7),
+            _ => return Err(invalid().into()),
+        };
+        let count: i64 = count.parse().map_err(|_| invalid())?;
+        Ok(QueryDate::Relative(count * days))
+    }
+
+    /// Like `parse`, but counts like `7d` go back from today instead of forward.
+    fn parse_past(input: &str) -> Result<Self, Box<dyn Error>> {
+        match (input, Self::parse(input)?) {
+            ("today" | "tomorrow" | "yesterday", date) => Ok(date),
+            (_, QueryDate::Relative(days)) => Ok(QueryDate::Relative(-days)),
+            (_, date) => Ok(date),
+        }
+    }
+
+    fn resolve(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            QueryDate::Absolute(date) => date,
+            QueryDate::Relative(days) => today + TimeDelta::days(days),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    /// `None` matches open and completed tasks
+    Completed(Option<bool>),
+    Tag(String),
+    Priority(Comparison, Priority),
+    Due(Comparison, QueryDate),
+    HasDue(bool),
+    /// Compares the completion date; tasks without one never match
+    CompletedOn(Comparison, QueryDate),
+    Overdue,
+    Depth(Comparison, u32),
+    Text(String),
+    Phrase(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Condition(Condition),
+    Not(Box<Expr>),
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+}
+
+/// A parsed filter. The empty query matches every task.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    source: String,
+    expr: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Not,
+    Or,
+    Word(String),
+    Phrase(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => { chars.next(); }
+            '(' => { chars.next(); tokens.push(Token::Open); }
+            ')' => { chars.next(); tokens.push(Token::Close); }
+            '-' => { chars.next(); tokens.push(Token::Not); }
+            '"' => {
+                chars.next();
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err("Unterminated quote".into()),
+                    }
+                }
+                tokens.push(Token::Phrase(phrase));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Splits `key<op>value`, if the word starts with a name followed by an operator.
+fn split_term(word: &str) -> Option<(&str, Comparison, &str)> {
+    let key_end = word.find(|c: char| !c.is_ascii_alphabetic())?;
+    if key_end == 0 {
+        return None;
+    }
+    let (key, rest) = word.split_at(key_end);
+    let operators = [
+        ("<=", Comparison::Le), (">=", Comparison::Ge), ("<", Comparison::Lt),
+        (">", Comparison::Gt), (":", Comparison::Eq), ("=", Comparison::Eq),
+    ];
+    operators.into_iter()
+        .find_map(|(op, comparison)| rest.strip_prefix(op).map(|value| (key, comparison, value)))
+}
+
+const FILTER_KEYS: [&str; 9] = ["status", "is", "tag", "priority", "pri", "due", "completed", "closed", "depth"];
+
+fn parse_term(word: &str) -> Result<Condition, Box<dyn Error>> {
+    let Some((key, comparison, value)) = split_term(word) else {
+        return Ok(Condition::Text(word.to_string()));
+    };
+    let key = key.to_lowercase();
+    if !FILTER_KEYS.contains(&key.as_str()) {
+        return Ok(Condition::Text(word.to_string()));
+    }
+    let value_lower = value.to_lowercase();
+    if value.is_empty() {
+        return Err(format!("Missing value in '{}'", word).into());
+    }
+    let only_equals = |condition: Condition| match comparison {
+        Comparison::Eq => Ok(condition),
+        _ => Err(format!("'{}' can only be compared with ':'", key).into()),
+    };
+
+    match key.as_str() {
+        "status" | "is" => only_equals(match value_lower.as_str() {
+            "open" | "pending" | "todo" => Condition::Completed(Some(false)),
+            "done" | "completed" => Condition::Completed(Some(true)),
+            "all" | "any" => Condition::Completed(None),
+            _ => return Err(format!("Invalid status '{}', expected open, done or all", value).into()),
+        }),
+        "tag" => only_equals(Condition::Tag(value.to_string())),
+        "priority" | "pri" => Ok(Condition::Priority(comparison, Priority::parse(value)?)),
+        "due" => match (comparison, value_lower.as_str()) {
+            (Comparison::Eq, "none") => Ok(Condition::HasDue(false)),
+            (Comparison::Eq, "any") => Ok(Condition::HasDue(true)),
+            (Comparison::Eq, "overdue") => Ok(Condition::Overdue),
+            _ => Ok(Condition::Due(comparison, QueryDate::parse(&value_lower)?)),
+        },
+        "completed" | "closed" => Ok(Condition::CompletedOn(comparison, QueryDate::parse_past(&value_lower)?)),
+        "depth" => {
+            let depth = value.parse().map_err(|_| format!("Invalid depth '{}'", value))?;
+            Ok(Condition::Depth(comparison, depth))
+        }
+        _ => unreachable!("'{}' is not in FILTER_KEYS", key),
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut terms = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            terms.push(self.parse_and()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Expr::Or(terms) })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, Box<dyn Error>> {
+        let mut terms = Vec::new();
+        while !matches!(self.peek(), None | Some(Token::Or | Token::Close)) {
+            terms.push(self.parse_unary()?);
+        }
+        match terms.len() {
+            0 => Err("Expected a filter term".into()),
+            1 => Ok(terms.remove(0)),
+            _ => Ok(Expr::And(terms)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, Box<dyn Error>> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.parse_unary()?))),
+            Some(Token::Open) => {
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err("Missing closing parenthesis".into()),
+                }
+            }
+            Some(Token::Word(word)) => Ok(Expr::Condition(parse_term(&word)?)),
+            Some(Token::Phrase(phrase)) => Ok(Expr::Condition(Condition::Phrase(phrase))),
+            _ => Err("Expected a filter term".into()),
+        }
+    }
+}
+
+/// Collects SQL parameters, numbering them in order.
+struct SqlParams {
+    values: Vec<String>,
+}
+
+impl SqlParams {
+    fn push(&mut self, value: String) -> String {
+        self.values.push(value);
+        format!("?{}", self.values.len())
+    }
+}
+
+impl Expr {
+    fn to_sql(&self, now: NaiveDateTime, params: &mut SqlParams) -> String {
+        match self {
+            Expr::Condition(condition) => condition.to_sql(now, params),
+            Expr::Not(expr) => format!("NOT ({})", expr.to_sql(now, params)),
+            Expr::And(terms) => Self::join(terms, " AND ", now, params),
+            Expr::Or(terms) => Self::join(terms, " OR ", now, params),
+        }
+    }
+
+    fn join(terms: &[Expr], separator: &str, now: NaiveDateTime, params: &mut SqlParams) -> String {
+        let parts: Vec<String> = terms.iter()
+            .map(|term| format!("({})", term.to_sql(now, params)))
+            .collect();
+        parts.join(separator)
+    }
+}
+
+impl Condition {
+    fn to_sql(&self, now: NaiveDateTime, params: &mut SqlParams) -> String {
+        let today = now.date();
+        match self {
+            Condition::Completed(None) => String::from("1"),
+            Condition::Completed(Some(completed)) => format!("Task.completed = {}", *completed as u8),
+            Condition::Tag(name) => {
+                let name = params.push(name.clone());
+                format!(
+                    "Task.id IN (SELECT TaskTag.task_id FROM TaskTag JOIN Tag ON Tag.id = TaskTag.tag_id
+                     WHERE Tag.name = {0} COLLATE NOCASE
+                        OR (substr(Tag.name, 1, 1) IN ('@', '#', '+') AND substr(Tag.name, 2) = {0} COLLATE NOCASE))",
+                    name)
+            }
+            Condition::Priority(comparison, priority) =>
+                format!("Task.priority {} {}", comparison.sql(), priority.to_db()),
+            Condition::Due(comparison, date) => {
+                let date = params.push(date.resolve(today).format("%Y-%m-%d").to_string());
+                format!("Task.due_date IS NOT NULL AND date(Task.due_date) {} {}", comparison.sql(), date)
+            }
+            Condition::HasDue(true) => String::from("Task.due_date IS NOT NULL"),
+            Condition::HasDue(false) => String::from("Task.due_date IS NULL"),
+            Condition::CompletedOn(comparison, date) => {
+                let date = params.push(date.resolve(today).format("%Y-%m-%d").to_string());
+                format!("Task.completed = 1 AND Task.completed_at IS NOT NULL AND date(Task.completed_at, 'localtime') {} {}",
+                    comparison.sql(), date)
+            }
+            Condition::Overdue => {
+                // Whole-day deadlines are overdue from the next day on
+                let today = params.push(today.format("%Y-%m-%d").to_string());
+                let now = params.push(now.format("%Y-%m-%d %H:%M:%S").to_string());
+                format!(
+                    "Task.completed = 0 AND Task.due_date IS NOT NULL AND CASE WHEN length(Task.due_date) = 10
+                     THEN Task.due_date < {} ELSE Task.due_date < {} END",
+                    today, now)
+            }
+            Condition::Depth(comparison, depth) => format!("Tree.depth {} {}", comparison.sql(), depth),
+            Condition::Text(word) => Self::search_sql(SearchHit::to_fts_query(word), params),
+            Condition::Phrase(phrase) => Self::search_sql(format!("\"{}\"", phrase.replace('"', "")), params),
+        }
+    }
+
+    fn search_sql(fts_query: String, params: &mut SqlParams) -> String {
+        if fts_query.trim_matches(['"', '*']).trim().is_empty() {
+            return String::from("1");
+        }
+        let fts_query = params.push(fts_query);
+        format!("Task.id IN (SELECT rowid FROM TaskSearch WHERE TaskSearch MATCH {})", fts_query)
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+        let source = input.trim().to_string();
+        let tokens = tokenize(&source)?;
+        if tokens.is_empty() {
+            return Ok(Self { source, expr: None });
+        }
+        let mut parser = Parser { tokens, position: 0 };
+        let expr = parser.parse_or()?;
+        if parser.peek().is_some() {
+            return Err("Unexpected closing parenthesis".into());
+        }
+        Ok(Self { source, expr: Some(expr) })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.expr.is_none()
+    }
+
+    pub fn expr(&self) -> Option<&Expr> {
+        self.expr.as_ref()
+    }
+
+    /// A statement selecting all columns of the matching tasks, with its
+    /// parameters. Relative dates count from `now`.
+    pub fn to_sql(&self, now: NaiveDateTime) -> (String, Vec<String>) {
+        let mut params = SqlParams { values: Vec::new() };
+        let condition = match &self.expr {
+            Some(expr) => expr.to_sql(now, &mut params),
+            None => String::from("1"),
+        };
+        let sql = format!(
+            "WITH RECURSIVE Tree(id, depth) AS (
+                 SELECT id, 0 FROM Task WHERE parent_id IS NULL AND deleted_at IS NULL AND archived_at IS NULL
+                 UNION ALL
+                 SELECT Task.id, Tree.depth + 1 FROM Task JOIN Tree ON Task.parent_id = Tree.id
+                 WHERE Task.deleted_at IS NULL AND Task.archived_at IS NULL
+             )
+             SELECT Task.* FROM Task JOIN Tree ON Tree.id = Task.id
+             WHERE {}",
+            condition);
+        (sql, params.values)
+    }
+}
+
+impl std::fmt::Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(condition: Condition) -> Expr {
+        Expr::Condition(condition)
+    }
+
+    #[test]
+    fn parse_example_query() -> Result<(), Box<dyn Error>> {
+        let query = Query::parse(r#"status:open tag:work due<7d depth<=2 "deploy""#)?;
+        assert_eq!(query.expr(), Some(&Expr::And(vec![
+            condition(Condition::Completed(Some(false))),
+            condition(Condition::Tag(String::from("work"))),
+            condition(Condition::Due(Comparison::Lt, QueryDate::Relative(7))),
+            condition(Condition::Depth(Comparison::Le, 2)),
+            condition(Condition::Phrase(String::from("deploy"))),
+        ])));
+        assert_eq!(query.to_string(), r#"status:open tag:work due<7d depth<=2 "deploy""#);
+        Ok(())
+    }
+
+    #[test]
+    fn parse_or_not_and_groups() -> Result<(), Box<dyn Error>> {
+        let query = Query::parse("-tag:home (priority>=high OR due:overdue) report")?;
+        assert_eq!(query.expr(), Some(&Expr::And(vec![
+            Expr::Not(Box::new(condition(Condition::Tag(String::from("home"))))),
+            Expr::Or(vec![
+                condition(Condition::Priority(Comparison::Ge, Priority::High)),
+                condition(Condition::Overdue),
+            ]),
+            condition(Condition::Text(String::from("report"))),
+        ])));
+        assert!(Query::parse("  ")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_queries() {
+        for input in ["status:maybe", "due<soon", "(tag:a", "tag:a)", "tag>a", "OR tag:a", "\"open"] {
+            assert!(Query::parse(input).is_err(), "{} should be rejected", input);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_text() -> Result<(), Box<dyn Error>> {
+        for input in ["re:deploy", "http://example.com", "colour:red", "x<y"] {
+            assert_eq!(Query::parse(input)?.expr(), Some(&Expr::Condition(Condition::Text(input.to_string()))));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn relative_dates_resolve_from_now() -> Result<(), Box<dyn Error>> {
+        let now = NaiveDateTime::parse_from_str("2025-03-01 12:00:00", "%Y-%m-%d %H:%M:%S")?;
+        let (sql, params) = Query::parse("due<2w OR due>=yesterday")?.to_sql(now);
+        assert!(sql.contains("date(Task.due_date) < ?1"));
+        assert_eq!(params, ["2025-03-15", "2025-02-28"]);
+
+        let (_, params) = Query::parse("completed>1w completed:yesterday")?.to_sql(now);
+        assert_eq!(params, ["2025-02-22", "2025-02-28"]);
+        Ok(())
+    }
+}
@@ -5,18 +5,42 @@ pub struct NavigationService {
 }
 
 impl NavigationService {
+    /// Navigation over the visible tasks: subtasks of collapsed tasks are skipped.
     pub fn new(tasks: &[TaskWithKids]) -> Self {
         let mut result = Vec::new();
         for root_task in tasks {
-            Self::flatten_task_tree(root_task, 0, Vec::new(), &mut result);
+            Self::flatten_task_tree(root_task, 0, Vec::new(), true, &mut result);
         }
         
         Self { flat_tasks: result }
     }
 
+    /// Every task in display order, including subtasks of collapsed tasks.
+    pub fn flatten_all(tasks: &[TaskWithKids]) -> Vec<FlatTask> {
+        let mut result = Vec::new();
+        for root_task in tasks {
+            Self::flatten_task_tree(root_task, 0, Vec::new(), false, &mut result);
+        }
+        result
+    }
+
+    pub fn flat_tasks(&self) -> &[FlatTask] {
+        &self.flat_tasks
+    }
+
     pub fn is_empty(&self) -> bool {
         self.flat_tasks.is_empty()
     }
+
+    pub fn len(&self) -> usize {
+        self.flat_tasks.len()
+    }
+
+    /// Id of the row at `index`, clamped to the last row.
+    pub fn get_id_at(&self, index: usize) -> Option<i32> {
+        self.flat_tasks.get(index.min(self.flat_tasks.len().saturating_sub(1)))
+            .map(|ft| ft.task.id as i32)
+    }
     
     pub fn get_next_id(&self, current_id: i32) -> Option<i32> {
         let current_index = self.find_index_by_id(current_id)?;
@@ -53,7 +77,7 @@ impl NavigationService {
             .map(|ft| ft.task.id as i32)
     }
     
-    fn find_index_by_id(&self, task_id: i32) -> Option<usize> {
+    pub fn find_index_by_id(&self, task_id: i32) -> Option<usize> {
         self.flat_tasks.iter()
             .position(|ft| ft.task.id as i32 == task_id)
     }
@@ -62,6 +86,7 @@ impl NavigationService {
         task_node: &TaskWithKids,
         depth: u32,
         parent_path: Vec<u32>,
+        skip_collapsed: bool,
         result: &mut Vec<FlatTask>,
     ) {
         let mut current_path = parent_path.clone();
@@ -74,8 +99,37 @@ impl NavigationService {
             index_in_parent: result.len(),
         });
         
+        if skip_collapsed && task_node.task.collapsed {
+            return;
+        }
         for subtask in &task_node.subtasks {
-            Self::flatten_task_tree(subtask, depth + 1, current_path.clone(), result);
+            Self::flatten_task_tree(subtask, depth + 1, current_path.clone(), skip_collapsed, result);
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, database::DB, task::{CreateTask, UpdateTask}};
+
+    #[test]
+    fn collapsed_subtasks_are_skipped() -> Result<(), Box<dyn std::error::Error>> {
+        let db = DB::new(&Config { db_path: String::from(":memory:"), trash_retention_days: 0 })?;
+        let create = |name: &str, parent_id| db.create_task(&CreateTask {
+            name: name.to_string(), parent_id, ..Default::default()
+        });
+        let parent = create("parent", None)?;
+        create("child", Some(parent as u32))?;
+        let last = create("last", None)?;
+        db.update_task(&UpdateTask { id: parent as u32, collapsed: Some(true), ..Default::default() })?;
+
+        let tasks = db.select_tasks_hierarchy(None)?;
+        let navigation = NavigationService::new(&tasks);
+        assert_eq!(navigation.get_next_id(parent), Some(last));
+        assert_eq!(navigation.get_previous_id(last), Some(parent));
+        assert_eq!(NavigationService::flatten_all(&tasks).len(), 3);
+        Ok(())
+    }
+}
@@ -1,20 +1,130 @@
-use std::error::Error;
+use std::{cell::Cell, collections::{HashMap, HashSet}, error::Error};
 
-use crate::{database::DB, task::*};
+use chrono::{Local, TimeDelta};
 
+use crate::{
+    database::DB, formats::{Format, ImportMode, ImportedTask}, history::{self, TaskChange}, query::Query, task::*
+};
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveDirection {
+    Up,
+    Down,
+}
+
+/// How many steps `undo` can go back.
+const UNDO_LIMIT: u32 = 100;
 
 pub struct TaskService {
     db: DB,
+    /// Set while a change is being recorded, so nested calls join it
+    recording: Cell<bool>,
 }
 
 impl TaskService {
     pub fn new(db: DB) -> Self {
-        Self { db }
+        Self { db, recording: Cell::new(false) }
+    }
+
+    /// Runs `f` in a transaction and records what it changed as one undoable step.
+    /// Compares every task, for changes that can touch any of them.
+    fn record<T>(&self, label: &str, f: impl FnOnce() -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+        self.record_scope(label, None, f)
+    }
+
+    /// Like [`Self::record`], but only compares the subtrees of `roots` and the
+    /// tasks `f` creates.
+    fn record_subtrees<T>(
+        &self, label: &str, roots: &[u32], f: impl FnOnce() -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        self.record_scope(label, Some(roots), f)
+    }
+
+    fn record_scope<T>(
+        &self, label: &str, roots: Option<&[u32]>, f: impl FnOnce() -> Result<T, Box<dyn Error>>,
+    ) -> Result<T, Box<dyn Error>> {
+        if self.recording.get() {
+            return f();
+        }
+        self.recording.set(true);
+        let result = self.db.transaction(|| {
+            let scope = match roots {
+                Some(roots) => Some((self.db.select_subtree_ids(roots)?, self.db.select_max_task_id()?)),
+                None => None,
+            };
+            let before = self.db.select_snapshots(scope.as_ref().map(|(ids, _)| ids))?;
+            let value = f()?;
+            let after = match (roots, scope) {
+                (Some(roots), Some((mut ids, max_id))) => {
+                    // Tasks that were in scope before and are gone now were deleted
+                    ids.extend(self.db.select_subtree_ids(roots)?);
+                    ids.extend(self.db.select_task_ids_after(max_id)?);
+                    self.db.select_snapshots(Some(&ids))?
+                }
+                _ => self.db.select_snapshots(None)?,
+            };
+            let changes = history::diff(before, after);
+            if !changes.is_empty() {
+                self.db.insert_history_step(label, &changes, UNDO_LIMIT)?;
+            }
+            Ok(value)
+        });
+        self.recording.set(false);
+        result
+    }
+
+    /// Reverts the latest step. Returns its label, or `None` if there's nothing to undo.
+    pub fn undo(&self) -> Result<Option<String>, Box<dyn Error>> {
+        self.db.transaction(|| {
+            let Some(step) = self.db.select_history_step(false)? else {
+                return Ok(None);
+            };
+            let inverse: Vec<TaskChange> = step.changes.iter().map(TaskChange::inverse).collect();
+            self.db.restore_snapshots(&inverse)?;
+            self.db.insert_step_events(&inverse, TaskEventKind::Undo, &step.label)?;
+            self.db.set_history_step_undone(step.id, true)?;
+            Ok(Some(step.label))
+        })
+    }
+
+    /// Applies the latest undone step again. Returns its label, or `None` if
+    /// there's nothing to redo.
+    pub fn redo(&self) -> Result<Option<String>, Box<dyn Error>> {
+        self.db.transaction(|| {
+            let Some(step) = self.db.select_history_step(true)? else {
+                return Ok(None);
+            };
+            self.db.restore_snapshots(&step.changes)?;
+            self.db.insert_step_events(&step.changes, TaskEventKind::Redo, &step.label)?;
+            self.db.set_history_step_undone(step.id, false)?;
+            Ok(Some(step.label))
+        })
     }
     
     pub fn load_hierarchy(&self) -> Result<Vec<TaskWithKids>, Box<dyn Error>> {
         self.db.select_tasks_hierarchy(None)
     }
+
+    /// Loads the tree keeping only tasks matching `filter` and their ancestors.
+    pub fn load_filtered_hierarchy(&self, filter: &TaskFilter) -> Result<Vec<TaskWithKids>, Box<dyn Error>> {
+        let tasks = self.load_hierarchy()?;
+        if filter.is_empty() {
+            return Ok(tasks);
+        }
+        let matching: Option<HashSet<u32>> = match filter.query.as_ref().filter(|query| !query.is_empty()) {
+            Some(query) => Some(self.find_tasks(query)?.iter().map(|task| task.id).collect()),
+            None => None,
+        };
+        Ok(TaskWithKids::filter(tasks, &|task| {
+            filter.matches_completion(task) && matching.as_ref().is_none_or(|ids| ids.contains(&task.id))
+        }))
+    }
+
+    /// The tasks matching `query` themselves, in no particular order.
+    pub fn find_tasks(&self, query: &Query) -> Result<Vec<Task>, Box<dyn Error>> {
+        self.db.select_tasks_matching(query)
+    }
     
     pub fn get_task_by_id(&self, id: u32) -> Result<Task, Box<dyn Error>> {
         let tasks = self.db.select_tasks(None)?;
@@ -26,51 +136,987 @@ impl TaskService {
     pub fn get_children(&self, parent_id: u32) -> Result<Vec<Task>, Box<dyn Error>> {
         self.db.select_task_subtasks(parent_id)
     }
+
+    /// Everything that happened to the task, oldest first. Trashed and archived
+    /// tasks have a history too.
+    pub fn history(&self, task_id: u32) -> Result<Vec<TaskEvent>, Box<dyn Error>> {
+        if !self.db.select_task_ids()?.contains(&task_id) {
+            return Err("Task not found".into());
+        }
+        self.db.select_task_events(task_id)
+    }
     
-    pub fn toggle_task_completion(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
-        let task = self.get_task_by_id(task_id)?;
-        self.db.update_task(&UpdateTask {
-            id: task_id,
-            name: None,
-            parent_id: None,
-            description: None,
-            completed: Some(if task.completed { 0 } else { 1 }),
+    /// Flips `completed`. Completing a recurring task also spawns its next occurrence,
+    /// whose id is returned.
+    pub fn toggle_task_completion(&self, task_id: u32) -> Result<Option<i32>, Box<dyn Error>> {
+        self.record_subtrees("Toggle completion", &[task_id], || {
+            let task = self.get_task_by_id(task_id)?;
+            let recurrence = match &task.recurrence {
+                Some(recurrence) if !task.completed => recurrence.clone(),
+                _ => {
+                    self.db.update_task(&UpdateTask {
+                        id: task_id,
+                        completed: Some(if task.completed { 0 } else { 1 }),
+                        ..Default::default()
+                    })?;
+                    return Ok(None);
+                }
+            };
+
+            self.db.transaction(|| {
+                // The rule moves to the new occurrence, so reopening and completing
+                // this one again doesn't spawn a duplicate
+                self.db.update_task(&UpdateTask {
+                    id: task_id,
+                    completed: Some(1),
+                    recurrence: Some(None),
+                    ..Default::default()
+                })?;
+                self.spawn_next_occurrence(&task, recurrence).map(Some)
+            })
+        })
+    }
+
+    fn spawn_next_occurrence(&self, task: &Task, recurrence: Recurrence) -> Result<i32, Box<dyn Error>> {
+        let base = task.due_date.map(|due| due.date()).unwrap_or(task.creation_date.date());
+        let next = recurrence.next_date(base, Local::now().date_naive());
+        let shift = next.signed_duration_since(base);
+
+        let tasks = self.load_hierarchy()?;
+        let node = TaskWithKids::find(&tasks, task.id).ok_or("Task not found")?;
+
+        let next_id = self.db.create_task(&CreateTask {
+            name: task.name.clone(),
+            parent_id: task.parent_id,
+            description: task.description.clone(),
+            due_date: task.due_date.map(|due| due.with_date(next)),
+            priority: task.priority,
+            recurrence: Some(recurrence),
+            ..Default::default()
+        })?;
+        self.db.set_task_tags(next_id as u32, &task.tags)?;
+
+        for subtask in &node.subtasks {
+            self.copy_subtree_reset(subtask, next_id as u32, shift)?;
+        }
+        Ok(next_id)
+    }
+
+    /// Copies `node` under `parent_id` as incomplete, moving due dates by `shift`.
+    /// Only the top task of an occurrence repeats, so copies don't keep their recurrence.
+    fn copy_subtree_reset(&self, node: &TaskWithKids, parent_id: u32, shift: TimeDelta) -> Result<(), Box<dyn Error>> {
+        let task = &node.task;
+        let id = self.db.create_task(&CreateTask {
+            name: task.name.clone(),
+            parent_id: Some(parent_id),
+            description: task.description.clone(),
+            due_date: task.due_date.map(|due| due.with_date(due.date() + shift)),
+            priority: task.priority,
+            ..Default::default()
         })?;
+        self.db.set_task_tags(id as u32, &task.tags)?;
+
+        for subtask in &node.subtasks {
+            self.copy_subtree_reset(subtask, id as u32, shift)?;
+        }
         Ok(())
     }
+
+    pub fn cycle_task_priority(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.record_subtrees("Change priority", &[task_id], || {
+            let task = self.get_task_by_id(task_id)?;
+            self.db.update_task(&UpdateTask {
+                id: task_id,
+                priority: Some(task.priority.next()),
+                ..Default::default()
+            })?;
+            Ok(())
+        })
+    }
     
     pub fn create_task(
         &self, 
         name: String, 
         parent_id: Option<u32>,
-        description: Option<String>
+        description: Option<String>,
+        due_date: Option<DueDate>,
+        priority: Priority,
+        recurrence: Option<Recurrence>,
     ) -> Result<i32, Box<dyn Error>> {
-        self.db.create_task(&CreateTask { parent_id, name, description })
+        self.add_task(CreateTask {
+            parent_id, name, description, due_date, priority, recurrence, ..Default::default()
+        }, &[])
+    }
+
+    /// Creates the task with its tags as one undoable step.
+    pub fn add_task(&self, task: CreateTask, tags: &[String]) -> Result<i32, Box<dyn Error>> {
+        self.record_subtrees("Add task", &[], || {
+            let id = self.db.create_task(&task)?;
+            if !tags.is_empty() {
+                self.db.set_task_tags(id as u32, tags)?;
+            }
+            Ok(id)
+        })
     }
     
     pub fn update_task(
         &self,
         task_id: u32,
         name: Option<String>,
-        description: Option<String>,
+        description: Option<Option<String>>,
+        due_date: Option<Option<DueDate>>,
+        priority: Option<Priority>,
+        recurrence: Option<Option<Recurrence>>,
     ) -> Result<bool, Box<dyn Error>> {
-        let update = UpdateTask {
+        self.edit_task(UpdateTask {
             id: task_id,
             name,
-            parent_id: None,
             description,
-            completed: None,
+            due_date,
+            priority,
+            recurrence,
+            ..Default::default()
+        })
+    }
+
+    /// Applies `update` as one undoable step. A new parent is checked like in
+    /// [`Self::reparent_task`]. Returns whether anything changed.
+    pub fn edit_task(&self, mut update: UpdateTask) -> Result<bool, Box<dyn Error>> {
+        self.record_subtrees("Edit task", &[update.id], || {
+            let moved = match update.parent_id.take() {
+                Some(parent_id) => self.reparent_task(update.id, parent_id)?,
+                None => false,
+            };
+            if !update.updated() {
+                return Ok(moved);
+            }
+            self.db.update_task(&update)?;
+            Ok(true)
+        })
+    }
+    
+    pub fn set_collapsed(&self, task_id: u32, collapsed: bool) -> Result<(), Box<dyn Error>> {
+        self.db.update_task(&UpdateTask {
+            id: task_id,
+            collapsed: Some(collapsed),
+            ..Default::default()
+        })
+    }
+
+    /// The task's siblings (including itself) in display order.
+    fn ordered_siblings(&self, task: &Task) -> Result<Vec<Task>, Box<dyn Error>> {
+        let mut siblings: Vec<Task> = self.db.select_tasks(None)?
+            .into_iter()
+            .filter(|t| t.parent_id == task.parent_id)
+            .collect();
+        siblings.sort_by_key(Task::sibling_order);
+        Ok(siblings)
+    }
+
+    /// Swaps the task with its neighbouring sibling. Siblings are ordered by
+    /// priority first, so a task only moves past siblings of the same priority.
+    /// Returns whether the task moved.
+    pub fn move_task(&self, task_id: u32, direction: MoveDirection) -> Result<bool, Box<dyn Error>> {
+        let task = self.get_task_by_id(task_id)?;
+        let siblings = self.ordered_siblings(&task)?;
+
+        let index = siblings.iter().position(|t| t.id == task_id).ok_or("Task not found")?;
+        let neighbour = match direction {
+            MoveDirection::Up => index.checked_sub(1).and_then(|i| siblings.get(i)),
+            MoveDirection::Down => siblings.get(index + 1),
         };
-        
-        if !update.updated() {
-            return Ok(false);
+
+        match neighbour {
+            Some(neighbour) if neighbour.priority == task.priority => {
+                self.record_subtrees("Move task", &[task_id, neighbour.id], || {
+                    self.db.swap_task_positions(task_id, neighbour.id)
+                })?;
+                Ok(true)
+            }
+            _ => Ok(false),
         }
-        
-        self.db.update_task(&update)?;
-        Ok(true)
     }
-    
+
+    /// Moves the task under `new_parent` (`None` for the top level), after its new siblings.
+    /// Fails if the new parent is the task itself or one of its descendants.
+    /// Returns whether the task moved.
+    pub fn reparent_task(&self, task_id: u32, new_parent: Option<u32>) -> Result<bool, Box<dyn Error>> {
+        self.record_subtrees("Move task", &[task_id], || {
+            let parents: HashMap<u32, Option<u32>> = self.db.select_tasks(None)?
+                .into_iter()
+                .map(|t| (t.id, t.parent_id))
+                .collect();
+            let current_parent = *parents.get(&task_id).ok_or("Task not found")?;
+
+            let mut ancestor = new_parent;
+            while let Some(id) = ancestor {
+                if id == task_id {
+                    return Err("A task can't be moved under itself or one of its subtasks".into());
+                }
+                ancestor = *parents.get(&id).ok_or(format!("Task {} not found", id))?;
+            }
+
+            if current_parent == new_parent {
+                return Ok(false);
+            }
+            self.db.update_task(&UpdateTask {
+                id: task_id,
+                parent_id: Some(new_parent),
+                ..Default::default()
+            })?;
+            Ok(true)
+        })
+    }
+
+    /// Makes the task a child of the sibling displayed right above it.
+    pub fn indent_task(&self, task_id: u32) -> Result<bool, Box<dyn Error>> {
+        self.record_subtrees("Indent task", &[task_id], || {
+            let task = self.get_task_by_id(task_id)?;
+            let siblings = self.ordered_siblings(&task)?;
+            let index = siblings.iter().position(|t| t.id == task_id).ok_or("Task not found")?;
+
+            match index.checked_sub(1).and_then(|i| siblings.get(i)) {
+                Some(previous) => self.reparent_task(task_id, Some(previous.id)),
+                None => Ok(false),
+            }
+        })
+    }
+
+    /// Moves the task up one level, next to its current parent.
+    pub fn outdent_task(&self, task_id: u32) -> Result<bool, Box<dyn Error>> {
+        self.record_subtrees("Outdent task", &[task_id], || {
+            let task = self.get_task_by_id(task_id)?;
+            match task.parent_id {
+                Some(parent_id) => {
+                    let parent = self.get_task_by_id(parent_id)?;
+                    self.reparent_task(task_id, parent.parent_id)
+                }
+                None => Ok(false),
+            }
+        })
+    }
+
+    /// Moves the task and its subtasks to the trash.
     pub fn delete_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
-        self.db.delete_task(task_id)
+        self.record_subtrees("Delete task", &[task_id], || {
+            self.db.delete_task(task_id)
+        })
+    }
+
+    /// The trashed tasks as trees, most recently deleted first. A trashed task
+    /// is a root unless its parent is in the trash as well.
+    pub fn load_trash(&self) -> Result<Vec<TaskWithKids>, Box<dyn Error>> {
+        Ok(TaskWithKids::forest(self.db.select_trashed_tasks()?))
+    }
+
+    /// The trashed task, if it can be restored or purged on its own.
+    fn trash_root(&self, task_id: u32) -> Result<Task, Box<dyn Error>> {
+        let trash = self.load_trash()?;
+        if let Some(node) = trash.into_iter().find(|node| node.task.id == task_id) {
+            return Ok(node.task);
+        }
+        match self.db.select_trashed_tasks()?.into_iter().find(|task| task.id == task_id) {
+            Some(task) => Err(format!(
+                "Task {} is inside trashed task {}, restore or purge that one", task_id,
+                task.parent_id.unwrap_or_default()).into()),
+            None => Err(format!("Task {} isn't in the trash", task_id).into()),
+        }
+    }
+
+    /// Takes a trashed task and its subtasks back to the list.
+    pub fn restore_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.record_subtrees("Restore task", &[task_id], || {
+            self.trash_root(task_id)?;
+            self.db.restore_task(task_id)
+        })
+    }
+
+    /// Archives a completed task together with its subtasks, which must all be
+    /// completed as well. The archived tasks keep their parents.
+    pub fn archive_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.record_subtrees("Archive task", &[task_id], || {
+            let tasks = self.load_hierarchy()?;
+            let node = TaskWithKids::find(&tasks, task_id).ok_or("Task not found")?;
+            if !node.fully_completed() {
+                return Err("Only tasks that are completed with all their subtasks can be archived".into());
+            }
+            self.db.archive_task(task_id)
+        })
+    }
+
+    /// Archives every top level task that is completed with all its subtasks.
+    /// Returns how many were archived.
+    pub fn archive_completed_roots(&self) -> Result<usize, Box<dyn Error>> {
+        let roots: Vec<u32> = self.load_hierarchy()?.iter()
+            .filter(|node| node.fully_completed())
+            .map(|node| node.task.id)
+            .collect();
+        self.record_subtrees("Archive completed tasks", &roots, || {
+            for id in &roots {
+                self.db.archive_task(*id)?;
+            }
+            Ok(roots.len())
+        })
+    }
+
+    /// The archived tasks as trees, most recently archived first.
+    pub fn load_archive(&self) -> Result<Vec<TaskWithKids>, Box<dyn Error>> {
+        Ok(TaskWithKids::forest(self.db.select_archived_tasks()?))
+    }
+
+    /// Takes an archived task and its subtasks back to the list.
+    pub fn unarchive_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.record_subtrees("Unarchive task", &[task_id], || {
+            if !self.load_archive()?.iter().any(|node| node.task.id == task_id) {
+                return match self.db.select_archived_tasks()?.into_iter().find(|task| task.id == task_id) {
+                    Some(task) => Err(format!(
+                        "Task {} is inside archived task {}, unarchive that one", task_id,
+                        task.parent_id.unwrap_or_default()).into()),
+                    None => Err(format!("Task {} isn't archived", task_id).into()),
+                };
+            }
+            self.db.unarchive_task(task_id)
+        })
+    }
+
+    /// Deletes a trashed task and its subtasks for good. This can't be undone.
+    pub fn purge_task(&self, task_id: u32) -> Result<(), Box<dyn Error>> {
+        self.trash_root(task_id)?;
+        self.db.purge_task(task_id)
+    }
+
+    /// Deletes everything in the trash for good. Returns the number of purged tasks.
+    pub fn empty_trash(&self) -> Result<usize, Box<dyn Error>> {
+        self.db.purge_trash(None)
+    }
+
+    pub fn export(&self, format: Format) -> Result<String, Box<dyn Error>> {
+        format.export(&self.load_hierarchy()?)
+    }
+
+    /// Parses `input` and imports it. Returns the number of imported tasks.
+    pub fn import(&self, format: Format, input: &str, mode: ImportMode) -> Result<usize, Box<dyn Error>> {
+        self.import_tasks(&format.import(input)?, mode)
+    }
+
+    /// Inserts imported trees in one transaction. Tasks whose uuid is already in
+    /// the database update that task instead. Returns the number of imported tasks.
+    pub fn import_tasks(&self, tasks: &[ImportedTask], mode: ImportMode) -> Result<usize, Box<dyn Error>> {
+        Self::check_unique_uuids(tasks, &mut HashSet::new())?;
+        self.record("Import", || {
+            self.db.transaction(|| {
+                if mode == ImportMode::Replace {
+                    self.db.trash_active_tasks()?;
+                }
+                let keep_ids = mode == ImportMode::Replace && Self::has_free_ids(tasks, &self.db.select_task_ids()?);
+                for task in tasks {
+                    self.insert_imported(task, None, keep_ids)?;
+                }
+                Ok(ImportedTask::count(tasks))
+            })
+        })
+    }
+
+    /// Fails if two tasks of the file share a uuid, as both would update the same task.
+    fn check_unique_uuids<'a>(tasks: &'a [ImportedTask], seen: &mut HashSet<&'a str>) -> Result<(), Box<dyn Error>> {
+        for task in tasks {
+            if let Some(uuid) = &task.task.uuid && !seen.insert(uuid) {
+                return Err(format!("Task '{}' has the same uuid {} as another task", task.task.name, uuid).into());
+            }
+            Self::check_unique_uuids(&task.subtasks, seen)?;
+        }
+        Ok(())
+    }
+
+    fn has_free_ids(tasks: &[ImportedTask], taken: &HashSet<u32>) -> bool {
+        fn collect(tasks: &[ImportedTask], ids: &mut Vec<Option<u32>>) {
+            for task in tasks {
+                ids.push(task.task.id);
+                collect(&task.subtasks, ids);
+            }
+        }
+        let mut ids = Vec::new();
+        collect(tasks, &mut ids);
+        let total = ids.len();
+        ids.sort();
+        ids.dedup();
+        ids.len() == total && ids.iter().all(|id| id.is_some_and(|id| !taken.contains(&id)))
+    }
+
+    fn insert_imported(&self, node: &ImportedTask, parent_id: Option<u32>, keep_ids: bool) -> Result<(), Box<dyn Error>> {
+        let existing = match &node.task.uuid {
+            Some(uuid) => self.db.select_task_by_uuid(uuid)?,
+            None => None,
+        };
+        let id = match existing {
+            Some(existing) => {
+                let task = &node.task;
+                // The imported task is active again; its subtasks only if the file has them too
+                if existing.deleted_at.is_some() || existing.archived_at.is_some() {
+                    self.db.revive_task(existing.id)?;
+                }
+                self.db.update_task(&UpdateTask {
+                    id: existing.id,
+                    name: Some(task.name.clone()),
+                    parent_id: (existing.parent_id != parent_id).then_some(parent_id),
+                    completed: Some(task.completed as u32),
+                    completed_at: task.completed_at,
+                    description: Some(task.description.clone()),
+                    due_date: Some(task.due_date),
+                    priority: Some(task.priority),
+                    recurrence: Some(task.recurrence.clone()),
+                    ..Default::default()
+                })?;
+                existing.id
+            }
+            None => self.db.create_task(&CreateTask {
+                id: if keep_ids { node.task.id } else { None },
+                parent_id,
+                ..node.task.clone()
+            })? as u32,
+        };
+        self.db.set_task_tags(id, &node.tags)?;
+        for subtask in &node.subtasks {
+            self.insert_imported(subtask, Some(id), keep_ids)?;
+        }
+        Ok(())
+    }
+
+    /// Searches names and descriptions for every word of `query`, best matches first.
+    pub fn search(&self, query: &str) -> Result<Vec<SearchHit>, Box<dyn Error>> {
+        let query = SearchHit::to_fts_query(query);
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.db.search_tasks(&query)
+    }
+
+    pub fn list_tags(&self) -> Result<Vec<Tag>, Box<dyn Error>> {
+        self.db.select_tags()
+    }
+
+    pub fn create_tag(&self, name: &str) -> Result<u32, Box<dyn Error>> {
+        self.db.create_tag(&Tag::normalize(name)?)
+    }
+
+    pub fn rename_tag(&self, old_name: &str, new_name: &str) -> Result<(), Box<dyn Error>> {
+        self.record("Rename tag", || {
+            self.db.rename_tag(old_name.trim(), &Tag::normalize(new_name)?)
+        })
+    }
+
+    pub fn delete_tag(&self, name: &str) -> Result<(), Box<dyn Error>> {
+        self.record("Delete tag", || {
+            self.db.delete_tag(name.trim())
+        })
+    }
+
+    pub fn add_task_tag(&self, task_id: u32, name: &str) -> Result<(), Box<dyn Error>> {
+        self.record_subtrees("Edit tags", &[task_id], || {
+            self.db.add_task_tag(task_id, &Tag::normalize(name)?)
+        })
+    }
+
+    pub fn remove_task_tag(&self, task_id: u32, name: &str) -> Result<(), Box<dyn Error>> {
+        self.record_subtrees("Edit tags", &[task_id], || {
+            self.db.remove_task_tag(task_id, name.trim())
+        })
+    }
+
+    pub fn set_task_tags(&self, task_id: u32, names: &[String]) -> Result<(), Box<dyn Error>> {
+        self.record_subtrees("Edit tags", &[task_id], || {
+            let names = names.iter()
+                .map(|name| Tag::normalize(name))
+                .collect::<Result<Vec<_>, _>>()?;
+            self.db.set_task_tags(task_id, &names)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use chrono::NaiveDate;
+
+    fn memory_service() -> Result<TaskService, Box<dyn Error>> {
+        Ok(TaskService::new(DB::new(&Config { db_path: String::from(":memory:"), trash_retention_days: 0 })?))
+    }
+
+    #[test]
+    fn completing_recurring_task_spawns_next() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        let due = DueDate::parse("2099-01-05 09:00")?;
+        let review = service.create_task(
+            String::from("Weekly review"), None, None, Some(due), Priority::High, Some(Recurrence::Daily))? as u32;
+        service.set_task_tags(review, &[String::from("@home")])?;
+        let inbox = service.create_task(
+            String::from("Empty inbox"), Some(review), None, None, Priority::None, None)? as u32;
+        service.toggle_task_completion(inbox)?;
+        service.create_task(
+            String::from("Stretch"), Some(review), None, None, Priority::None, Some(Recurrence::Daily))?;
+
+        let next = service.toggle_task_completion(review)?.ok_or("No occurrence spawned")? as u32;
+
+        let done = service.get_task_by_id(review)?;
+        assert!(done.completed);
+        assert_eq!(done.recurrence, None);
+
+        let spawned = service.get_task_by_id(next)?;
+        assert!(!spawned.completed);
+        assert_eq!(spawned.due_date, Some(DueDate::parse("2099-01-06 09:00")?));
+        assert_eq!(spawned.recurrence, Some(Recurrence::Daily));
+        assert_eq!(spawned.priority, Priority::High);
+        assert_eq!(spawned.tags, ["@home"]);
+
+        let children = service.get_children(next)?;
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].name, "Empty inbox");
+        assert!(!children[0].completed);
+        assert_eq!(children[1].recurrence, None);
+
+        // Reopening and completing the old occurrence again doesn't spawn another copy
+        service.toggle_task_completion(review)?;
+        assert_eq!(service.toggle_task_completion(review)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn undated_recurring_task_stays_undated() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        let water = service.create_task(
+            String::from("Water plants"), None, None, None, Priority::None, Some(Recurrence::Daily))? as u32;
+        let next = service.toggle_task_completion(water)?.ok_or("No occurrence spawned")? as u32;
+        let spawned = service.get_task_by_id(next)?;
+        assert_eq!(spawned.due_date, None);
+        assert_eq!(spawned.recurrence, Some(Recurrence::Daily));
+        Ok(())
+    }
+
+    #[test]
+    fn reparenting_rejects_cycles() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        let a = service.create_task(String::from("a"), None, None, None, Priority::None, None)? as u32;
+        let b = service.create_task(String::from("b"), None, None, None, Priority::None, None)? as u32;
+        let c = service.create_task(String::from("c"), Some(b), None, None, Priority::None, None)? as u32;
+
+        assert!(service.reparent_task(b, Some(b)).is_err());
+        assert!(service.reparent_task(b, Some(c)).is_err());
+
+        assert!(service.indent_task(b)?);
+        assert_eq!(service.get_task_by_id(b)?.parent_id, Some(a));
+        assert!(!service.indent_task(b)?);
+
+        assert!(service.outdent_task(c)?);
+        assert_eq!(service.get_task_by_id(c)?.parent_id, Some(a));
+        assert!(service.reparent_task(c, None)?);
+        assert_eq!(service.get_task_by_id(c)?.parent_id, None);
+        assert!(!service.outdent_task(c)?);
+        Ok(())
+    }
+
+    #[test]
+    fn adding_and_editing_are_single_steps() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        let parent = service.create_task(String::from("Parent"), None, None, None, Priority::None, None)? as u32;
+        let task = CreateTask { name: String::from("Draft"), description: Some(String::from("notes")), ..Default::default() };
+        let id = service.add_task(task, &[String::from("@work")])? as u32;
+        assert_eq!(service.get_task_by_id(id)?.tags, ["@work"]);
+
+        let update = UpdateTask { id, parent_id: Some(Some(parent)), description: Some(None), ..Default::default() };
+        assert!(service.edit_task(update)?);
+        let edited = service.get_task_by_id(id)?;
+        assert_eq!((edited.parent_id, edited.description), (Some(parent), None));
+        assert!(service.edit_task(UpdateTask { id: parent, parent_id: Some(Some(id)), ..Default::default() }).is_err());
+
+        assert_eq!(service.undo()?.as_deref(), Some("Edit task"));
+        let restored = service.get_task_by_id(id)?;
+        assert_eq!((restored.parent_id, restored.description.as_deref()), (None, Some("notes")));
+        assert_eq!(service.undo()?.as_deref(), Some("Add task"));
+        assert_eq!(service.load_hierarchy()?.len(), 1);
+        Ok(())
+    }
+
+    fn sample_tree(service: &TaskService) -> Result<(), Box<dyn Error>> {
+        let project = service.create_task(
+            String::from("Project"), None, Some(String::from("Line one\nLine two")),
+            Some(DueDate::parse("2030-05-01")?), Priority::High, None)? as u32;
+        service.set_task_tags(project, &[String::from("@work"), String::from("+launch")])?;
+        let done = service.create_task(String::from("Done step"), Some(project), None, None, Priority::None, None)? as u32;
+        service.toggle_task_completion(done)?;
+        service.create_task(
+            String::from("Next step"), Some(project), None, None, Priority::Low,
+            Some(Recurrence::Weekly(vec![chrono::Weekday::Mon])))?;
+        service.create_task(String::from("Errand"), None, None, None, Priority::None, None)?;
+        Ok(())
+    }
+
+    #[test]
+    fn formats_round_trip() -> Result<(), Box<dyn Error>> {
+        let source = memory_service()?;
+        sample_tree(&source)?;
+        let uuid = source.get_task_by_id(1)?.uuid.ok_or("No uuid")?;
+        // DTSTAMP in iCalendar files is the time of the export
+        let without_stamps = |exported: &str| -> String {
+            exported.split("\r\n").filter(|line| !line.starts_with("DTSTAMP:")).collect::<Vec<&str>>().join("\r\n")
+        };
+
+        for format in Format::ALL {
+            let exported = source.export(format)?;
+            let target = memory_service()?;
+            assert_eq!(target.import(format, &exported, ImportMode::Replace)?, 4, "{}", format.name());
+            assert_eq!(without_stamps(&target.export(format)?), without_stamps(&exported), "{}", format.name());
+
+            // Formats that keep the uuid update the same tasks when imported again
+            if exported.contains(&uuid) {
+                assert_eq!(target.import(format, &exported, ImportMode::Merge)?, 4);
+                assert_eq!(target.load_hierarchy()?.len(), 2, "{}", format.name());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn merging_without_uuids_adds_copies() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        sample_tree(&service)?;
+        let exported = service.export(Format::Json)?;
+
+        let anonymous = exported.lines().filter(|line| !line.contains("\"uuid\"")).collect::<Vec<&str>>().join("\n");
+        assert_eq!(service.import(Format::Json, &anonymous, ImportMode::Merge)?, 4);
+        let roots = service.load_hierarchy()?;
+        let projects: Vec<&TaskWithKids> = roots.iter().filter(|t| t.task.name == "Project").collect();
+        assert_eq!(roots.len(), 4);
+        assert_eq!(projects.len(), 2);
+        assert_ne!(projects[0].task.id, projects[1].task.id);
+        assert_eq!(projects[1].subtasks.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn replacing_moves_the_list_to_the_trash() -> Result<(), Box<dyn Error>> {
+        let source = memory_service()?;
+        sample_tree(&source)?;
+        let exported = source.export(Format::Json)?;
+
+        let target = memory_service()?;
+        let project = target.create_task(String::from("Old project"), None, None, None, Priority::None, None)? as u32;
+        let trashed = target.create_task(String::from("Trashed"), Some(project), None, None, Priority::None, None)? as u32;
+        target.delete_task(trashed)?;
+        let archived = target.create_task(String::from("Archived"), None, None, None, Priority::None, None)? as u32;
+        target.toggle_task_completion(archived)?;
+        target.archive_task(archived)?;
+
+        assert_eq!(target.import(Format::Json, &exported, ImportMode::Replace)?, 4);
+        // The replaced project goes to the trash, with the subtask trashed before
+        let trash = target.load_trash()?;
+        assert_eq!((trash.len(), trash[0].task.id, trash[0].subtasks[0].task.id), (1, project, trashed));
+        assert_eq!(target.history(project)?.last().map(|event| event.kind), Some(TaskEventKind::Delete));
+        assert_eq!(target.load_archive()?[0].task.id, archived);
+        // The file's ids are taken, so the imported tasks get new ones
+        let roots = target.load_hierarchy()?;
+        assert_eq!(roots.len(), 2);
+        assert!(roots.iter().all(|root| root.task.id > archived));
+        Ok(())
+    }
+
+    #[test]
+    fn reimporting_archived_task_unarchives_it() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        let project = service.create_task(String::from("Project"), None, None, None, Priority::None, None)? as u32;
+        service.toggle_task_completion(project)?;
+        let exported = service.export(Format::Org)?;
+        service.archive_task(project)?;
+
+        let reopened = format!("{}** TODO New step\n", exported.replace("* DONE Project", "* TODO Project"));
+        assert_eq!(service.import(Format::Org, &reopened, ImportMode::Merge)?, 2);
+        let roots = service.load_hierarchy()?;
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].task.id, project);
+        assert_eq!(roots[0].subtasks[0].task.name, "New step");
+        assert!(service.load_archive()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn reimport_updates_only_the_matched_tasks() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        let id = service.create_task(String::from("Water plants"), None, None, None, Priority::None, Some(Recurrence::Daily))? as u32;
+        let step = service.create_task(String::from("Fill can"), Some(id), None, None, Priority::None, None)? as u32;
+        let uuid = service.get_task_by_id(id)?.uuid.ok_or("No uuid")?;
+        service.delete_task(id)?;
+
+        let completed_at = NaiveDate::from_ymd_opt(2030, 1, 2).unwrap().and_hms_opt(8, 0, 0).unwrap();
+        let imported = ImportedTask {
+            task: CreateTask {
+                name: String::from("Water plants"),
+                uuid: Some(uuid),
+                completed: true,
+                completed_at: Some(completed_at),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        service.import_tasks(std::slice::from_ref(&imported), ImportMode::Merge)?;
+        let task = service.get_task_by_id(id)?;
+        assert_eq!((task.recurrence, task.completed_at), (None, Some(completed_at)));
+        // The subtask isn't in the file, so it stays in the trash
+        assert_eq!(service.load_trash()?[0].task.id, step);
+
+        assert!(service.import_tasks(&[imported.clone(), imported], ImportMode::Merge).is_err());
+        Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn query_filter_keeps_ancestors() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        sample_tree(&service)?;
+        let names = |query: &str| -> Result<Vec<String>, Box<dyn Error>> {
+            let filter = TaskFilter { query: Some(Query::parse(query)?), ..Default::default() };
+            let tasks = crate::services::navigation_service::NavigationService::flatten_all(
+                &service.load_filtered_hierarchy(&filter)?);
+            Ok(tasks.into_iter().map(|flat| flat.task.name).collect())
+        };
+
+        assert_eq!(names("status:done")?, ["Project", "Done step"]);
+        assert_eq!(names("tag:work depth:0")?, ["Project"]);
+        assert_eq!(names("depth>=1 -priority:low")?, ["Project", "Done step"]);
+        assert_eq!(names("\"line two\" OR errand")?, ["Project", "Errand"]);
+        assert_eq!(names("due:none status:open")?, ["Project", "Next step", "Errand"]);
+        assert_eq!(names("due>2030-05-01")?, Vec::<String>::new());
+        assert_eq!(names("")?.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn completion_and_query_filters_combine() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        sample_tree(&service)?;
+        let mut filter = TaskFilter { query: Some(Query::parse("depth:1")?), ..Default::default() };
+        filter.cycle_completed();
+        filter.cycle_completed();
+        assert_eq!(filter.to_string(), "completed tasks matching depth:1");
+
+        // The open parent is only there to hold the completed subtask
+        let tasks = service.load_filtered_hierarchy(&filter)?;
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].task.name, "Project");
+        let names: Vec<&str> = tasks[0].subtasks.iter().map(|t| t.task.name.as_str()).collect();
+        assert_eq!(names, ["Done step"]);
+
+        filter.cycle_completed();
+        assert_eq!(filter.completed, None);
+        Ok(())
+    }
+
+    #[test]
+    fn undo_restores_deleted_subtree() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        sample_tree(&service)?;
+        let original = service.export(Format::Csv)?;
+        let project = service.load_hierarchy()?[0].task.id;
+
+        service.delete_task(project)?;
+        assert_eq!(service.load_hierarchy()?.len(), 1);
+        assert_eq!(service.undo()?.as_deref(), Some("Delete task"));
+        assert_eq!(service.export(Format::Csv)?, original);
+        assert_eq!(service.search("two")?.len(), 1);
+
+        assert_eq!(service.redo()?.as_deref(), Some("Delete task"));
+        assert_eq!(service.load_hierarchy()?.len(), 1);
+        assert_eq!(service.redo()?, None);
+        service.undo()?;
+        let events: Vec<String> = service.history(project)?.iter().rev().take(2).map(ToString::to_string).collect();
+        assert_eq!(events, ["Undone: Delete task", "Redone: Delete task"]);
+
+        // A new change drops the steps that could be redone
+        service.cycle_task_priority(project)?;
+        assert_eq!(service.redo()?, None);
+        assert_eq!(service.undo()?.as_deref(), Some("Change priority"));
+        assert_eq!(service.export(Format::Csv)?, original);
+
+        // Back through every step of sample_tree
+        while service.undo()?.is_some() {}
+        assert!(service.load_hierarchy()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn steps_only_store_the_tasks_in_scope() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        sample_tree(&service)?;
+        let original = service.export(Format::Csv)?;
+        let roots = service.load_hierarchy()?;
+        let next_step = roots[0].subtasks.iter().find(|t| t.task.recurrence.is_some()).unwrap().task.id;
+
+        let spawned = service.toggle_task_completion(next_step)?.ok_or("No occurrence spawned")? as u32;
+        let step = service.db.select_history_step(false)?.ok_or("No step recorded")?;
+        let ids: Vec<u32> = step.changes.iter()
+            .map(|change| change.before.as_ref().or(change.after.as_ref()).unwrap().id)
+            .collect();
+        assert_eq!(ids, [next_step, spawned]);
+
+        let chore = service.create_task(String::from("Chore"), None, None, None, Priority::None, None)? as u32;
+        assert!(service.move_task(chore, MoveDirection::Up)?);
+        let names: Vec<String> = service.load_hierarchy()?.into_iter().map(|t| t.task.name).collect();
+        assert_eq!(names, ["Project", "Chore", "Errand"]);
+
+        assert_eq!(service.undo()?.as_deref(), Some("Move task"));
+        assert_eq!(service.undo()?.as_deref(), Some("Add task"));
+        assert_eq!(service.undo()?.as_deref(), Some("Toggle completion"));
+        assert_eq!(service.export(Format::Csv)?, original);
+        Ok(())
+    }
+
+    #[test]
+    fn undo_history_survives_restart() -> Result<(), Box<dyn Error>> {
+        let path = std::env::temp_dir().join(format!("todolist-undo-{}.db", std::process::id()));
+        let config = Config { db_path: path.to_string_lossy().to_string(), trash_retention_days: 0 };
+        let _ = std::fs::remove_file(&path);
+
+        let service = TaskService::new(DB::new(&config)?);
+        let id = service.create_task(String::from("Recurring"), None, None, None, Priority::None, Some(Recurrence::Daily))?;
+        service.toggle_task_completion(id as u32)?;
+        assert_eq!(service.load_hierarchy()?.len(), 2);
+        drop(service);
+
+        let service = TaskService::new(DB::new(&config)?);
+        assert_eq!(service.undo()?.as_deref(), Some("Toggle completion"));
+        let tasks = service.load_hierarchy()?;
+        assert_eq!(tasks.len(), 1);
+        assert!(!tasks[0].task.completed);
+        assert_eq!(tasks[0].task.recurrence, Some(Recurrence::Daily));
+
+        drop(service);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn deleted_tasks_go_to_the_trash() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        sample_tree(&service)?;
+        let project = service.load_hierarchy()?.remove(0);
+        let next_step = project.subtasks[0].task.id;
+
+        service.delete_task(next_step)?;
+        service.delete_task(project.task.id)?;
+        assert_eq!(service.load_hierarchy()?.len(), 1);
+        assert!(service.search("line")?.is_empty());
+        assert!(service.list_tags()?.iter().all(|tag| tag.task_count == 0));
+
+        // The subtask deleted first now sits inside its trashed parent
+        let trash = service.load_trash()?;
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].subtasks.len(), 2);
+        assert!(service.restore_task(next_step).is_err());
+
+        // Whether the subtask comes back too depends on its deletion time,
+        // which database::tests checks
+        service.restore_task(project.task.id)?;
+        assert_eq!(service.load_hierarchy()?.len(), 2);
+
+        service.delete_task(project.task.id)?;
+        assert!(service.purge_task(next_step).is_err());
+        assert!(!service.history(next_step)?.is_empty());
+        service.purge_task(project.task.id)?;
+        assert!(service.history(next_step).is_err());
+        assert!(service.load_trash()?.is_empty());
+        assert!(service.restore_task(project.task.id).is_err());
+
+        let errand = service.load_hierarchy()?[0].task.id;
+        service.delete_task(errand)?;
+        assert_eq!(service.empty_trash()?, 1);
+        assert!(service.load_hierarchy()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn undo_does_not_bring_back_purged_tasks() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        let kept = service.create_task(String::from("Kept"), None, None, None, Priority::None, None)? as u32;
+        let secret = service.create_task(String::from("Secret"), None, None, None, Priority::None, None)? as u32;
+        service.create_task(String::from("Secret step"), Some(secret), None, None, Priority::None, None)?;
+        service.update_task(kept, Some(String::from("Renamed")), None, None, None, None)?;
+        service.delete_task(secret)?;
+        service.purge_task(secret)?;
+
+        // Only the edit and adding the kept task are left to undo
+        assert_eq!(service.undo()?.as_deref(), Some("Edit task"));
+        assert_eq!(service.undo()?.as_deref(), Some("Add task"));
+        assert_eq!(service.undo()?, None);
+        assert!(service.load_hierarchy()?.is_empty());
+        assert!(service.load_trash()?.is_empty());
+
+        while service.redo()?.is_some() {}
+        let names: Vec<String> = service.load_hierarchy()?.into_iter().map(|t| t.task.name).collect();
+        assert_eq!(names, ["Renamed"]);
+        Ok(())
+    }
+
+    #[test]
+    fn completion_time_is_tracked() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        let id = service.create_task(String::from("Report"), None, None, None, Priority::None, None)? as u32;
+        assert_eq!(service.get_task_by_id(id)?.completed_at, None);
+
+        service.toggle_task_completion(id)?;
+        let completed_at = service.get_task_by_id(id)?.completed_at.ok_or("completion time not set")?;
+        assert!(chrono::Utc::now().naive_utc() - completed_at < TimeDelta::minutes(1));
+        let recent = |query: &str| -> Result<usize, Box<dyn Error>> {
+            Ok(service.find_tasks(&Query::parse(query)?)?.len())
+        };
+        assert_eq!(recent("completed>=1d")?, 1);
+        assert_eq!(recent("completed<1d")?, 0);
+
+        service.toggle_task_completion(id)?;
+        assert_eq!(service.get_task_by_id(id)?.completed_at, None);
+        assert_eq!(recent("completed>=1d")?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn completed_subtrees_are_archived() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        sample_tree(&service)?;
+        let project = service.load_hierarchy()?.remove(0);
+        let done_step = project.subtasks[1].task.id;
+
+        assert!(service.archive_task(project.task.id).is_err());
+        service.archive_task(done_step)?;
+        assert_eq!(service.load_hierarchy()?[0].subtasks.len(), 1);
+        let archive = service.load_archive()?;
+        assert_eq!(archive[0].task.parent_id, Some(project.task.id));
+        assert!(service.unarchive_task(project.task.id).is_err());
+
+        let errand = service.load_hierarchy()?[1].task.id;
+        service.toggle_task_completion(errand)?;
+        assert_eq!(service.archive_completed_roots()?, 1);
+        assert_eq!(service.load_hierarchy()?.len(), 1);
+        assert!(service.search("errand")?.is_empty());
+        assert_eq!(service.load_archive()?.len(), 2);
+
+        service.unarchive_task(done_step)?;
+        assert_eq!(service.load_hierarchy()?[0].subtasks.len(), 2);
+        assert_eq!(service.undo()?.as_deref(), Some("Unarchive task"));
+        assert_eq!(service.load_archive()?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn moving_stays_within_priority() -> Result<(), Box<dyn Error>> {
+        let service = memory_service()?;
+        let urgent = service.create_task(String::from("urgent"), None, None, None, Priority::Urgent, None)? as u32;
+        let a = service.create_task(String::from("a"), None, None, None, Priority::None, None)? as u32;
+        let b = service.create_task(String::from("b"), None, None, None, Priority::None, None)? as u32;
+
+        assert!(service.move_task(b, MoveDirection::Up)?);
+        assert!(!service.move_task(b, MoveDirection::Up)?);
+        assert!(!service.move_task(urgent, MoveDirection::Up)?);
+        assert!(!service.move_task(a, MoveDirection::Down)?);
+
+        let names: Vec<String> = service.load_hierarchy()?.into_iter().map(|t| t.task.name).collect();
+        assert_eq!(names, ["urgent", "b", "a"]);
+        Ok(())
+    }
+}
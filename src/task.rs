@@ -1,10 +1,28 @@
-use std::{collections::HashMap, error::Error};
+use std::{collections::{HashMap, HashSet}, error::Error};
 
 use rusqlite::Row;
-use chrono::NaiveDateTime;
+use chrono::{Datelike, Days, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
 
+use crate::query::Query;
 
-#[derive(Debug, Clone)]
+
+/// Format of timestamps stored by SQLite's `CURRENT_TIMESTAMP`.
+pub const DB_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Converts a stored UTC timestamp to local time, e.g. for display.
+pub fn utc_to_local(utc: NaiveDateTime) -> NaiveDateTime {
+    Utc.from_utc_datetime(&utc).with_timezone(&Local).naive_local()
+}
+
+/// Converts a local time to UTC for storing. Times skipped by a clock change
+/// are kept as they are.
+pub fn local_to_utc(local: NaiveDateTime) -> NaiveDateTime {
+    Local.from_local_datetime(&local).earliest()
+        .map(|date_time| date_time.naive_utc())
+        .unwrap_or(local)
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Task {
     pub id: u32,
     pub parent_id: Option<u32>,
@@ -12,6 +30,22 @@ pub struct Task {
     pub completed: bool,
     pub description: Option<String>,
     pub creation_date: chrono::NaiveDateTime,
+    pub due_date: Option<DueDate>,
+    pub priority: Priority,
+    pub tags: Vec<String>,
+    pub recurrence: Option<Recurrence>,
+    pub position: u32,
+    pub collapsed: bool,
+    /// Stable identity shared with other tools, assigned on creation.
+    /// Tasks from before it existed got one when the database was migrated.
+    pub uuid: Option<String>,
+    /// When the task was moved to the trash, in UTC
+    pub deleted_at: Option<NaiveDateTime>,
+    /// When the task was archived, in UTC
+    pub archived_at: Option<NaiveDateTime>,
+    /// When the task was last completed, in UTC. `None` for open tasks
+    /// and for tasks whose completion time isn't known, e.g. some imports.
+    pub completed_at: Option<NaiveDateTime>,
 }
 
 
@@ -31,16 +65,539 @@ impl Task {
     pub fn from_select(select: SelectTask) -> Result<Self, Box<dyn Error>> {
         let parse_date = NaiveDateTime::parse_from_str(
             &select.creation_date, 
-            "%Y-%m-%d %H:%M:%S")?;
+            DB_DATE_FORMAT)?;
         Ok(Self {
             id: select.id,
             parent_id: select.parent_id,
             name: select.name,
             completed: select.completed,
             description: select.description,
-            creation_date: parse_date
+            creation_date: parse_date,
+            due_date: select.due_date.as_deref().map(DueDate::parse).transpose()?,
+            priority: Priority::from_db(select.priority)?,
+            tags: Vec::new(),
+            recurrence: select.recurrence.as_deref().map(Recurrence::from_rrule).transpose()?,
+            position: select.position,
+            collapsed: select.collapsed,
+            uuid: select.uuid,
+            deleted_at: select.deleted_at.as_deref()
+                .map(|date| NaiveDateTime::parse_from_str(date, DB_DATE_FORMAT))
+                .transpose()?,
+            archived_at: select.archived_at.as_deref()
+                .map(|date| NaiveDateTime::parse_from_str(date, DB_DATE_FORMAT))
+                .transpose()?,
+            completed_at: select.completed_at.as_deref()
+                .map(|date| NaiveDateTime::parse_from_str(date, DB_DATE_FORMAT))
+                .transpose()?,
         })
     }
+
+    /// Sort key for siblings: higher priority first, then the manual position.
+    pub fn sibling_order(&self) -> (std::cmp::Reverse<Priority>, u32) {
+        (std::cmp::Reverse(self.priority), self.position)
+    }
+}
+
+/// A deadline either for a whole day or for an exact moment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DueDate {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DueStatus {
+    Overdue,
+    Today,
+    Upcoming,
+}
+
+impl DueDate {
+    /// Accepts `YYYY-MM-DD`, `YYYY-MM-DD HH:MM` and `YYYY-MM-DD HH:MM:SS`.
+    pub fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+        let input = input.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+            return Ok(DueDate::Date(date));
+        }
+        for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+            if let Ok(date_time) = NaiveDateTime::parse_from_str(input, format) {
+                return Ok(DueDate::DateTime(date_time));
+            }
+        }
+        Err(format!("Invalid due date '{}', expected YYYY-MM-DD [HH:MM]", input).into())
+    }
+
+    pub fn to_db_string(&self) -> String {
+        match self {
+            DueDate::Date(date) => date.format("%Y-%m-%d").to_string(),
+            DueDate::DateTime(date_time) => date_time.format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+
+    pub fn date(&self) -> NaiveDate {
+        match self {
+            DueDate::Date(date) => *date,
+            DueDate::DateTime(date_time) => date_time.date(),
+        }
+    }
+
+    /// The same deadline moved to `date`, keeping the time of day if there is one.
+    pub fn with_date(&self, date: NaiveDate) -> Self {
+        match self {
+            DueDate::Date(_) => DueDate::Date(date),
+            DueDate::DateTime(date_time) => DueDate::DateTime(date.and_time(date_time.time())),
+        }
+    }
+
+    pub fn status(&self, now: NaiveDateTime) -> DueStatus {
+        let overdue = match self {
+            DueDate::Date(date) => *date < now.date(),
+            DueDate::DateTime(date_time) => *date_time < now,
+        };
+        if overdue {
+            DueStatus::Overdue
+        } else if self.date() == now.date() {
+            DueStatus::Today
+        } else {
+            DueStatus::Upcoming
+        }
+    }
+}
+
+impl std::fmt::Display for DueDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DueDate::Date(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            DueDate::DateTime(date_time) => write!(f, "{}", date_time.format("%Y-%m-%d %H:%M")),
+        }
+    }
+}
+
+/// How a task repeats. Stored as an RRULE-style string, e.g. `FREQ=WEEKLY;BYDAY=MO,WE`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Recurrence {
+    Daily,
+    /// On the given weekdays; empty means the weekday of the current due date.
+    Weekly(Vec<Weekday>),
+    /// On the given day of the month, clamped to the length of short months.
+    Monthly(u32),
+    /// N days after the task was completed, regardless of its due date.
+    AfterCompletion(u32),
+}
+
+const RRULE_DAYS: [(Weekday, &str); 7] = [
+    (Weekday::Mon, "MO"), (Weekday::Tue, "TU"), (Weekday::Wed, "WE"), (Weekday::Thu, "TH"),
+    (Weekday::Fri, "FR"), (Weekday::Sat, "SA"), (Weekday::Sun, "SU"),
+];
+
+impl Recurrence {
+    /// Parses user input: `daily`, `weekly [mon,wed]`, `monthly N` or `every N days`.
+    pub fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+        let input = input.trim().to_lowercase();
+        let (kind, rest) = input.split_once(' ').unwrap_or((&input, ""));
+        let rest = rest.trim();
+        let invalid = || format!(
+            "Invalid recurrence '{}', expected daily, weekly [mon,wed], monthly N or every N days", input);
+
+        match kind {
+            "daily" if rest.is_empty() => Ok(Recurrence::Daily),
+            "weekly" => {
+                let mut days = Vec::new();
+                for day in rest.split([',', ' ']).filter(|d| !d.is_empty()) {
+                    let day: Weekday = day.parse().map_err(|_| invalid())?;
+                    if !days.contains(&day) {
+                        days.push(day);
+                    }
+                }
+                days.sort_by_key(|d| d.num_days_from_monday());
+                Ok(Recurrence::Weekly(days))
+            },
+            "monthly" => match rest.parse::<u32>() {
+                Ok(day) if (1..=31).contains(&day) => Ok(Recurrence::Monthly(day)),
+                _ => Err(invalid().into()),
+            },
+            "every" => {
+                let count = rest.trim_end_matches(|c: char| c.is_alphabetic() || c == ' ');
+                let unit = rest[count.len()..].trim();
+                match (count.parse::<u32>(), unit) {
+                    (Ok(n), "d" | "day" | "days") if n > 0 => Ok(Recurrence::AfterCompletion(n)),
+                    _ => Err(invalid().into()),
+                }
+            },
+            _ => Err(invalid().into()),
+        }
+    }
+
+    /// Parses the rules [`Self::to_rrule`] writes. Anything else, like `COUNT`
+    /// or an `INTERVAL` other than 1, is an error rather than being dropped.
+    pub fn from_rrule(rule: &str) -> Result<Self, Box<dyn Error>> {
+        let mut parts = HashMap::new();
+        for part in rule.split(';') {
+            let (key, value) = part.split_once('=')
+                .ok_or(format!("Invalid recurrence rule '{}'", rule))?;
+            if !["FREQ", "BYDAY", "BYMONTHDAY", "FROM", "INTERVAL"].contains(&key) {
+                return Err(format!("Unsupported {} in recurrence rule '{}'", key, rule).into());
+            }
+            parts.insert(key, value);
+        }
+        if !parts.contains_key("FROM") && parts.get("INTERVAL").is_some_and(|interval| *interval != "1") {
+            return Err(format!("Unsupported interval in recurrence rule '{}'", rule).into());
+        }
+        let number = |key: &str| -> Result<u32, Box<dyn Error>> {
+            let value = parts.get(key).ok_or(format!("Recurrence rule '{}' is missing {}", rule, key))?;
+            Ok(value.parse()?)
+        };
+
+        match (parts.get("FREQ").copied(), parts.get("FROM").copied()) {
+            (Some("DAILY"), Some("COMPLETION")) => Ok(Recurrence::AfterCompletion(number("INTERVAL")?)),
+            (Some("DAILY"), None) => Ok(Recurrence::Daily),
+            (Some("WEEKLY"), None) => {
+                let days = match parts.get("BYDAY") {
+                    Some(days) => days.split(',')
+                        .map(|code| RRULE_DAYS.iter()
+                            .find(|(_, c)| *c == code)
+                            .map(|(day, _)| *day)
+                            .ok_or(format!("Invalid weekday '{}' in recurrence rule", code)))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    None => Vec::new(),
+                };
+                Ok(Recurrence::Weekly(days))
+            },
+            (Some("MONTHLY"), None) => Ok(Recurrence::Monthly(number("BYMONTHDAY")?)),
+            _ => Err(format!("Unsupported recurrence rule '{}'", rule).into()),
+        }
+    }
+
+    pub fn to_rrule(&self) -> String {
+        match self {
+            Recurrence::Daily => String::from("FREQ=DAILY"),
+            Recurrence::Weekly(days) if days.is_empty() => String::from("FREQ=WEEKLY"),
+            Recurrence::Weekly(days) => {
+                let codes: Vec<&str> = days.iter()
+                    .filter_map(|day| RRULE_DAYS.iter().find(|(d, _)| d == day).map(|(_, code)| *code))
+                    .collect();
+                format!("FREQ=WEEKLY;BYDAY={}", codes.join(","))
+            },
+            Recurrence::Monthly(day) => format!("FREQ=MONTHLY;BYMONTHDAY={}", day),
+            Recurrence::AfterCompletion(days) => format!("FREQ=DAILY;INTERVAL={};FROM=COMPLETION", days),
+        }
+    }
+
+    /// The date of the next occurrence of a task due on `base` and completed on `today`.
+    ///
+    /// Calendar based rules pick the first matching date after both `base` and `today`,
+    /// so finishing an overdue chore doesn't spawn another overdue copy.
+    pub fn next_date(&self, base: NaiveDate, today: NaiveDate) -> NaiveDate {
+        let after = base.max(today);
+        let first_matching = |matches: &dyn Fn(NaiveDate) -> bool| {
+            after.iter_days().skip(1).find(|d| matches(*d)).unwrap_or(after)
+        };
+
+        match self {
+            Recurrence::Daily => after + Days::new(1),
+            Recurrence::Weekly(days) if days.is_empty() => first_matching(&|d| d.weekday() == base.weekday()),
+            Recurrence::Weekly(days) => first_matching(&|d| days.contains(&d.weekday())),
+            Recurrence::Monthly(day) => first_matching(&|d| d.day() == (*day).min(Self::days_in_month(d))),
+            Recurrence::AfterCompletion(days) => today + Days::new(*days as u64),
+        }
+    }
+
+    fn days_in_month(date: NaiveDate) -> u32 {
+        let first_of_next = date.with_day(1).unwrap() + chrono::Months::new(1);
+        first_of_next.pred_opt().unwrap().day()
+    }
+}
+
+impl std::fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Recurrence::Daily => write!(f, "daily"),
+            Recurrence::Weekly(days) if days.is_empty() => write!(f, "weekly"),
+            Recurrence::Weekly(days) => {
+                let names: Vec<String> = days.iter().map(|d| d.to_string()).collect();
+                write!(f, "weekly on {}", names.join(", "))
+            },
+            Recurrence::Monthly(day) => write!(f, "monthly on day {}", day),
+            Recurrence::AfterCompletion(days) => write!(f, "every {} days after completion", days),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 5] = [
+        Priority::None, Priority::Low, Priority::Medium, Priority::High, Priority::Urgent
+    ];
+
+    pub fn from_db(value: u8) -> Result<Self, Box<dyn Error>> {
+        Self::ALL.get(value as usize)
+            .copied()
+            .ok_or(format!("Invalid priority value {}", value).into())
+    }
+
+    pub fn to_db(self) -> u8 {
+        self as u8
+    }
+
+    /// Accepts a level name, its first letter or its number (0-4).
+    pub fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+        let input = input.trim().to_lowercase();
+        if let Ok(value) = input.parse::<u8>() {
+            return Self::from_db(value);
+        }
+        Self::ALL.into_iter()
+            .find(|p| p.label().to_lowercase() == input || p.label()[..1].to_lowercase() == input)
+            .ok_or(format!("Invalid priority '{}', expected none/low/medium/high/urgent", input).into())
+    }
+
+    /// The next level, wrapping from `Urgent` back to `None`.
+    pub fn next(self) -> Self {
+        Self::ALL[(self as usize + 1) % Self::ALL.len()]
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Priority::None => "None",
+            Priority::Low => "Low",
+            Priority::Medium => "Medium",
+            Priority::High => "High",
+            Priority::Urgent => "Urgent",
+        }
+    }
+}
+
+impl std::fmt::Display for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub id: u32,
+    pub name: String,
+    pub task_count: u32,
+}
+
+impl Tag {
+    /// Trims a user supplied tag name. Sigils such as `@work` or `#urgent` are kept as typed.
+    pub fn normalize(input: &str) -> Result<String, Box<dyn Error>> {
+        let name = input.trim();
+        if name.is_empty() || name.chars().any(|c| c.is_whitespace() || c == ',') {
+            return Err(format!("Invalid tag '{}': tags can't be empty or contain spaces or commas", name).into());
+        }
+        Ok(name.to_string())
+    }
+
+    /// Splits a comma and/or space separated list of tags.
+    pub fn parse_list(input: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut tags: Vec<String> = Vec::new();
+        for part in input.split([',', ' ']).filter(|p| !p.trim().is_empty()) {
+            let name = Self::normalize(part)?;
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(&name)) {
+                tags.push(name);
+            }
+        }
+        Ok(tags)
+    }
+}
+
+/// What a task view shows. See [`Query`] for the filter language.
+#[derive(Debug, Clone, Default)]
+pub struct TaskFilter {
+    /// `Some(false)` shows only open tasks, `Some(true)` only completed ones
+    pub completed: Option<bool>,
+    pub query: Option<Query>,
+}
+
+impl TaskFilter {
+    pub fn is_empty(&self) -> bool {
+        self.completed.is_none() && self.query.as_ref().is_none_or(Query::is_empty)
+    }
+
+    /// Switches between all, open and completed tasks.
+    pub fn cycle_completed(&mut self) {
+        self.completed = match self.completed {
+            None => Some(false),
+            Some(false) => Some(true),
+            Some(true) => None,
+        };
+    }
+
+    pub fn matches_completion(&self, task: &Task) -> bool {
+        self.completed.is_none_or(|completed| task.completed == completed)
+    }
+}
+
+impl std::fmt::Display for TaskFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.completed {
+            None => write!(f, "all tasks")?,
+            Some(false) => write!(f, "open tasks")?,
+            Some(true) => write!(f, "completed tasks")?,
+        }
+        match self.query.as_ref().filter(|query| !query.is_empty()) {
+            Some(query) => write!(f, " matching {}", query),
+            None => Ok(()),
+        }
+    }
+}
+
+/// What happened to a task in a `TaskEvent`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TaskEventKind {
+    Create,
+    Update,
+    Complete,
+    Reopen,
+    Delete,
+    Restore,
+    Archive,
+    Unarchive,
+    /// A step of the undo history was undone, its label is the new value
+    Undo,
+    Redo,
+}
+
+impl TaskEventKind {
+    pub const ALL: [TaskEventKind; 10] = [
+        TaskEventKind::Create, TaskEventKind::Update, TaskEventKind::Complete,
+        TaskEventKind::Reopen, TaskEventKind::Delete, TaskEventKind::Restore,
+        TaskEventKind::Archive, TaskEventKind::Unarchive, TaskEventKind::Undo, TaskEventKind::Redo,
+    ];
+
+    pub fn to_db(self) -> &'static str {
+        match self {
+            TaskEventKind::Create => "create",
+            TaskEventKind::Update => "update",
+            TaskEventKind::Complete => "complete",
+            TaskEventKind::Reopen => "reopen",
+            TaskEventKind::Delete => "delete",
+            TaskEventKind::Restore => "restore",
+            TaskEventKind::Archive => "archive",
+            TaskEventKind::Unarchive => "unarchive",
+            TaskEventKind::Undo => "undo",
+            TaskEventKind::Redo => "redo",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Result<Self, Box<dyn Error>> {
+        Self::ALL.into_iter()
+            .find(|kind| kind.to_db() == value)
+            .ok_or(format!("Invalid task event '{}'", value).into())
+    }
+}
+
+/// An entry in a task's audit history. An update that changes several
+/// fields is recorded as one event per field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskEvent {
+    pub id: u32,
+    pub task_id: u32,
+    pub kind: TaskEventKind,
+    /// The changed column of an update, e.g. `name` or `parent_id`
+    pub field: Option<String>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    /// When it happened, in UTC
+    pub created_at: NaiveDateTime,
+}
+
+impl std::fmt::Display for TaskEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let value = |value: &Option<String>| value.clone().unwrap_or(String::from("none"));
+        match (self.kind, self.field.as_deref()) {
+            (TaskEventKind::Create, _) => write!(f, "Created"),
+            (TaskEventKind::Complete, _) => write!(f, "Completed"),
+            (TaskEventKind::Reopen, _) => write!(f, "Reopened"),
+            (TaskEventKind::Delete, _) => write!(f, "Moved to the trash"),
+            (TaskEventKind::Restore, _) => write!(f, "Restored from the trash"),
+            (TaskEventKind::Archive, _) => write!(f, "Archived"),
+            (TaskEventKind::Unarchive, _) => write!(f, "Taken out of the archive"),
+            (TaskEventKind::Undo, _) => write!(f, "Undone: {}", value(&self.new_value)),
+            (TaskEventKind::Redo, _) => write!(f, "Redone: {}", value(&self.new_value)),
+            (TaskEventKind::Update, Some("name")) =>
+                write!(f, "Renamed from \"{}\" to \"{}\"", value(&self.old_value), value(&self.new_value)),
+            (TaskEventKind::Update, Some("description")) => write!(f, "Description changed"),
+            (TaskEventKind::Update, Some("parent_id")) => {
+                let parent = |id: &Option<String>| match id {
+                    Some(id) => format!("task {}", id),
+                    None => String::from("the top level"),
+                };
+                write!(f, "Moved from {} to {}", parent(&self.old_value), parent(&self.new_value))
+            }
+            (TaskEventKind::Update, field) => {
+                let label = match field {
+                    Some("due_date") => "Due date",
+                    Some("priority") => "Priority",
+                    Some("recurrence") => "Repeat",
+                    Some(other) => other,
+                    None => "Task",
+                };
+                write!(f, "{} changed from {} to {}", label, value(&self.old_value), value(&self.new_value))
+            }
+        }
+    }
+}
+
+/// A task matching a full-text search.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub task_id: u32,
+    /// The whole name, with matches wrapped in `MATCH_START` and `MATCH_END`
+    pub name: String,
+    /// An excerpt of the best matching field, marked the same way
+    pub snippet: String,
+    /// bm25 score, lower is a better match
+    pub rank: f64,
+}
+
+impl SearchHit {
+    pub const MATCH_START: char = '\u{2}';
+    pub const MATCH_END: char = '\u{3}';
+
+    /// Splits marked text into `(text, is_match)` parts.
+    pub fn segments(text: &str) -> Vec<(&str, bool)> {
+        let mut segments = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find(Self::MATCH_START) {
+            if start > 0 {
+                segments.push((&rest[..start], false));
+            }
+            let after = &rest[start + Self::MATCH_START.len_utf8()..];
+            let end = after.find(Self::MATCH_END).unwrap_or(after.len());
+            segments.push((&after[..end], true));
+            rest = after.get(end + Self::MATCH_END.len_utf8()..).unwrap_or("");
+        }
+        if !rest.is_empty() {
+            segments.push((rest, false));
+        }
+        segments
+    }
+
+    pub fn name_matches(&self) -> bool {
+        self.name.contains(Self::MATCH_START)
+    }
+
+    /// Turns what the user typed into an FTS5 query: every word must match,
+    /// as a prefix so results show up while typing.
+    pub fn to_fts_query(input: &str) -> String {
+        let terms: Vec<String> = input.split_whitespace()
+            .map(|term| term.replace('"', ""))
+            .filter(|term| !term.is_empty())
+            .map(|term| format!("\"{}\"*", term))
+            .collect();
+        terms.join(" ")
+    }
 }
 
 pub struct TaskWithKids {
@@ -75,25 +632,104 @@ impl TaskWithKids {
         }
 
     }
+
+    pub fn count_descendants(&self) -> usize {
+        self.subtasks.iter().map(|sub| 1 + sub.count_descendants()).sum()
+    }
+
+    /// Builds trees out of some of the tasks, e.g. the trashed ones. A task is
+    /// a root unless its parent is among `tasks` too; roots keep their order.
+    pub fn forest(tasks: Vec<Task>) -> Vec<TaskWithKids> {
+        let ids: HashSet<u32> = tasks.iter().map(|task| task.id).collect();
+
+        let mut roots = Vec::new();
+        let mut by_parent: HashMap<Option<u32>, Vec<Task>> = HashMap::new();
+        for task in tasks {
+            match task.parent_id {
+                Some(parent_id) if ids.contains(&parent_id) => by_parent.entry(task.parent_id).or_default().push(task),
+                _ => roots.push(task),
+            }
+        }
+        for siblings in by_parent.values_mut() {
+            siblings.sort_by_key(Task::sibling_order);
+        }
+        roots.into_iter().map(|task| Self::get_recursive(task, &by_parent)).collect()
+    }
+
+    /// Whether the task and all its subtasks are completed.
+    pub fn fully_completed(&self) -> bool {
+        self.task.completed && self.subtasks.iter().all(Self::fully_completed)
+    }
+
+    pub fn find(tasks: &[TaskWithKids], task_id: u32) -> Option<&TaskWithKids> {
+        tasks.iter().find_map(|node| {
+            if node.task.id == task_id {
+                Some(node)
+            } else {
+                Self::find(&node.subtasks, task_id)
+            }
+        })
+    }
+
+    /// Keeps the tasks matching `predicate` together with all of their ancestors,
+    /// so a match deep in the tree is still shown under its parents.
+    pub fn filter(tasks: Vec<TaskWithKids>, predicate: &impl Fn(&Task) -> bool) -> Vec<TaskWithKids> {
+        tasks.into_iter()
+            .filter_map(|node| {
+                let subtasks = Self::filter(node.subtasks, predicate);
+                if predicate(&node.task) || !subtasks.is_empty() {
+                    Some(TaskWithKids { task: node.task, subtasks })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
 }
 
+#[derive(Debug, Clone, Default)]
 pub struct CreateTask {
+    /// Forces the row id, e.g. when restoring a backup. `None` picks the next free id.
+    pub id: Option<u32>,
     pub name: String,
     pub parent_id: Option<u32>,
     pub description: Option<String>,
+    pub due_date: Option<DueDate>,
+    pub priority: Priority,
+    pub recurrence: Option<Recurrence>,
+    pub completed: bool,
+    /// `None` means now.
+    pub creation_date: Option<NaiveDateTime>,
+    /// `None` generates a new one.
+    pub uuid: Option<String>,
+    /// UTC. Left unset when `None`, even for completed tasks.
+    pub completed_at: Option<NaiveDateTime>,
+    pub collapsed: bool,
 }
 
+/// Fields left as `None` are not changed. `Some(None)` clears a nullable field,
+/// e.g. `parent_id: Some(None)` moves the task to the top level.
+#[derive(Default)]
 pub struct UpdateTask {
     pub id: u32,
     pub name: Option<String>,
-    pub parent_id: Option<u32>,
+    pub parent_id: Option<Option<u32>>,
     pub completed: Option<u32>,
-    pub description: Option<String>,
+    /// UTC completion time written along with `completed`. `None` keeps the
+    /// current one, or takes the current time for a task being completed.
+    pub completed_at: Option<NaiveDateTime>,
+    pub description: Option<Option<String>>,
+    pub due_date: Option<Option<DueDate>>,
+    pub priority: Option<Priority>,
+    pub recurrence: Option<Option<Recurrence>>,
+    pub collapsed: Option<bool>,
 }
 
 impl UpdateTask {
     pub fn updated(&self) -> bool {
         self.name.is_some() || self.parent_id.is_some() || self.completed.is_some() || self.description.is_some() 
+            || self.due_date.is_some() || self.priority.is_some() || self.recurrence.is_some()
+            || self.collapsed.is_some()
     }
 }
 
@@ -104,6 +740,15 @@ pub struct SelectTask {
     pub completed: bool,
     pub description: Option<String>,
     pub creation_date: String, 
+    pub due_date: Option<String>,
+    pub priority: u8,
+    pub recurrence: Option<String>,
+    pub position: u32,
+    pub collapsed: bool,
+    pub uuid: Option<String>,
+    pub deleted_at: Option<String>,
+    pub archived_at: Option<String>,
+    pub completed_at: Option<String>,
 }
 
 impl SelectTask {
@@ -114,7 +759,125 @@ impl SelectTask {
             name: row.get(2)?,
             completed: row.get(3)?,
             description: row.get(4)?,
-            creation_date: row.get(5)?
+            creation_date: row.get(5)?,
+            due_date: row.get(6)?,
+            priority: row.get(7)?,
+            recurrence: row.get(8)?,
+            position: row.get(9)?,
+            collapsed: row.get(10)?,
+            uuid: row.get(11)?,
+            deleted_at: row.get(12)?,
+            archived_at: row.get(13)?,
+            completed_at: row.get(14)?,
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(input: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn due_date_parsing() -> Result<(), Box<dyn Error>> {
+        assert!(matches!(DueDate::parse("2025-03-01")?, DueDate::Date(_)));
+        assert!(matches!(DueDate::parse("2025-03-01 14:30")?, DueDate::DateTime(_)));
+        assert!(DueDate::parse("tomorrow").is_err());
+
+        let due = DueDate::parse("2025-03-01 14:30")?;
+        assert_eq!(DueDate::parse(&due.to_db_string())?, due);
+        Ok(())
+    }
+
+    #[test]
+    fn due_date_status() -> Result<(), Box<dyn Error>> {
+        let now = at("2025-03-01 12:00");
+        assert_eq!(DueDate::parse("2025-02-28")?.status(now), DueStatus::Overdue);
+        assert_eq!(DueDate::parse("2025-03-01")?.status(now), DueStatus::Today);
+        assert_eq!(DueDate::parse("2025-03-01 11:00")?.status(now), DueStatus::Overdue);
+        assert_eq!(DueDate::parse("2025-03-01 18:00")?.status(now), DueStatus::Today);
+        assert_eq!(DueDate::parse("2025-03-02")?.status(now), DueStatus::Upcoming);
+        Ok(())
+    }
+
+    fn date(input: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(input, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn recurrence_parsing() -> Result<(), Box<dyn Error>> {
+        let cases = [
+            ("daily", Recurrence::Daily),
+            ("weekly wed, mon", Recurrence::Weekly(vec![Weekday::Mon, Weekday::Wed])),
+            ("weekly", Recurrence::Weekly(Vec::new())),
+            ("monthly 31", Recurrence::Monthly(31)),
+            ("every 3 days", Recurrence::AfterCompletion(3)),
+            ("every 10d", Recurrence::AfterCompletion(10)),
+        ];
+        for (input, expected) in cases {
+            let parsed = Recurrence::parse(input)?;
+            assert_eq!(parsed, expected);
+            assert_eq!(Recurrence::from_rrule(&parsed.to_rrule())?, expected);
+        }
+        assert!(Recurrence::parse("monthly 32").is_err());
+        assert!(Recurrence::parse("every 0 days").is_err());
+        assert!(Recurrence::parse("hourly").is_err());
+        assert_eq!(Recurrence::from_rrule("FREQ=DAILY;INTERVAL=1")?, Recurrence::Daily);
+        let unsupported = [
+            "FREQ=DAILY;INTERVAL=3", "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO", "FREQ=DAILY;COUNT=5",
+            "FREQ=MONTHLY;BYMONTHDAY=1;UNTIL=20300101",
+        ];
+        for rule in unsupported {
+            assert!(Recurrence::from_rrule(rule).is_err(), "{}", rule);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn recurrence_next_date() {
+        // 2025-03-03 is a Monday
+        let monday = date("2025-03-03");
+        assert_eq!(Recurrence::Daily.next_date(monday, monday), date("2025-03-04"));
+        assert_eq!(Recurrence::Daily.next_date(monday, date("2025-03-10")), date("2025-03-11"));
+
+        let weekly = Recurrence::Weekly(vec![Weekday::Mon, Weekday::Thu]);
+        assert_eq!(weekly.next_date(monday, monday), date("2025-03-06"));
+        assert_eq!(Recurrence::Weekly(Vec::new()).next_date(monday, monday), date("2025-03-10"));
+
+        assert_eq!(Recurrence::Monthly(31).next_date(date("2025-01-31"), date("2025-01-31")), date("2025-02-28"));
+        assert_eq!(Recurrence::Monthly(15).next_date(date("2025-01-15"), date("2025-01-10")), date("2025-02-15"));
+
+        assert_eq!(Recurrence::AfterCompletion(3).next_date(monday, date("2025-03-05")), date("2025-03-08"));
+    }
+
+    #[test]
+    fn tag_list_parsing() -> Result<(), Box<dyn Error>> {
+        assert_eq!(Tag::parse_list("@work, #urgent home")?, ["@work", "#urgent", "home"]);
+        assert_eq!(Tag::parse_list("a,A")?, ["a"]);
+        assert!(Tag::parse_list("")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn search_snippet_segments() {
+        let marked = format!("Deploy {}api{} now", SearchHit::MATCH_START, SearchHit::MATCH_END);
+        assert_eq!(SearchHit::segments(&marked), [("Deploy ", false), ("api", true), (" now", false)]);
+        assert_eq!(SearchHit::to_fts_query(r#" dep "api "#), r#""dep"* "api"*"#);
+        assert_eq!(SearchHit::to_fts_query("\""), "");
+    }
+
+    #[test]
+    fn priority_parsing_and_cycling() -> Result<(), Box<dyn Error>> {
+        assert_eq!(Priority::parse("high")?, Priority::High);
+        assert_eq!(Priority::parse("U")?, Priority::Urgent);
+        assert_eq!(Priority::parse("2")?, Priority::Medium);
+        assert!(Priority::parse("later").is_err());
+
+        assert_eq!(Priority::High.next(), Priority::Urgent);
+        assert_eq!(Priority::Urgent.next(), Priority::None);
+        Ok(())
+    }
+}
@@ -190,18 +190,12 @@ impl TodoList {
     }
 
     pub fn toggle_completed(&self, id: u32) -> Result<(), Box<dyn Error>> {
-        let current = self.get_selected_task();
-        match current {
-            Some((t, _)) => {
-                self.db.update_task(&UpdateTask { 
-                    id, 
-                    name: None, 
-                    parent_id: None, 
-                    description: None, 
-                    completed: Some(!t.completed as u32),
-                })?
-            }
-            None => ()
+        if let Some((t, _)) = self.get_selected_task() {
+            self.db.update_task(&UpdateTask { 
+                id, 
+                completed: Some(!t.completed as u32),
+                ..Default::default()
+            })?
         }
         Ok(())
     }
@@ -216,7 +210,7 @@ impl TodoList {
         parent_id: Option<u32>,
         description: Option<String>
      ) -> Result<i32, Box<dyn Error>> {
-        self.db.create_task(&CreateTask { parent_id, name, description })
+        self.db.create_task(&CreateTask { parent_id, name, description, ..Default::default() })
     }
 
     pub fn update_task(
@@ -227,7 +221,7 @@ impl TodoList {
         completed: Option<u32>,
         description: Option<String>,
     ) -> Result<bool, Box<dyn Error>> {
-        let update = UpdateTask { id, name, parent_id, description, completed };
+        let update = UpdateTask { id, name, parent_id: parent_id.map(Some), description: description.map(Some), completed, ..Default::default() };
         if !update.updated() {
             return Ok(false)
         }
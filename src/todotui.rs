@@ -1,83 +1,236 @@
-use std::error::Error;
+use std::{collections::HashSet, error::Error};
 
 use crossterm::{
-    event::KeyCode, queue, style::Print
+    event::{KeyCode, KeyEvent, KeyModifiers}, queue, style::Print, terminal
 };
 use colored::Colorize;
 
-use crate::{config::Config, database::DB, services::{navigation_service::NavigationService, task_service::TaskService}, task::*, ui::{input::InputHandler, task_renderer::TaskRenderer, terminal::TerminalRenderer}};
+use crate::{config::Config, database::DB, query::Query, services::{navigation_service::NavigationService, task_service::{MoveDirection, TaskService}}, task::*, ui::{input::{InputEvent, InputHandler}, task_renderer::TaskRenderer, terminal::TerminalRenderer, viewport::Viewport}};
 
 
+/// The active `/` search. Hits are looked up again on every refresh, so they
+/// follow edits.
+struct Search {
+    query: String,
+    hits: Vec<SearchHit>,
+    current: usize,
+}
+
+/// A choice on the trash screen.
+enum TrashAction {
+    Restore(u32),
+    Purge(u32),
+    Empty,
+}
+
+impl TrashAction {
+    fn parse(input: &str) -> Result<Self, Box<dyn Error>> {
+        let parse_id = |id: &str| id.trim().parse::<u32>().map_err(|_| format!("Invalid task id '{}'", id.trim()));
+        match input.split_once(' ') {
+            Some(("p", id)) => Ok(TrashAction::Purge(parse_id(id)?)),
+            None if input == "e" => Ok(TrashAction::Empty),
+            _ => Ok(TrashAction::Restore(parse_id(input)?)),
+        }
+    }
+}
+
 pub struct TodoTUI {
     task_service: TaskService,
+    /// The filtered tree on screen, loaded once per refresh
+    tasks: Vec<TaskWithKids>,
     navigation: NavigationService,
     renderer: TerminalRenderer,
+    viewport: Viewport,
+    filter: TaskFilter,
+    search: Option<Search>,
+    /// Collapsed tasks opened to show a search match. Their saved state is kept,
+    /// so they fold again when the search changes.
+    revealed: HashSet<u32>,
+    /// Shown above the list until the next key press, e.g. what was undone
+    notice: Option<String>,
+    /// Like `notice`, for an action that failed
+    error: Option<String>,
+    trash_retention_days: u32,
     selected_id: i32,
     running: bool,
 }
 
 impl TodoTUI {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        let task_service = TaskService::new(DB::new(&Config::build()?)?);
+        let config = Config::build()?;
+        let task_service = TaskService::new(DB::new(&config)?);
         let tasks = task_service.load_hierarchy()?;
         let navigation = NavigationService::new(&tasks);
         let renderer = TerminalRenderer::new();
         
         Ok(Self {
             task_service,
+            tasks,
             selected_id: navigation.get_first_id().unwrap_or(-1),
             navigation,
             renderer,
+            viewport: Viewport::new(),
+            filter: TaskFilter::default(),
+            search: None,
+            revealed: HashSet::new(),
+            notice: None,
+            error: None,
+            trash_retention_days: config.trash_retention_days,
             running: true,
         })
     }
 
     pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         self.renderer.enter_raw_mode()?;
-        
+        let result = self.main_loop();
+        // The terminal is restored even when the loop fails
+        self.renderer.exit_raw_mode()?;
+        result
+    }
+
+    fn main_loop(&mut self) -> Result<(), Box<dyn Error>> {
         while self.running {
             self.refresh_navigation()?;
             self.render_main_view()?;
-            self.handle_main_events()?;
+            let key = match InputHandler::read_event()? {
+                InputEvent::Key(key) => key,
+                InputEvent::Resize => continue, // Re-rendered on the next pass
+            };
+            if let Err(e) = self.handle_key(key) {
+                self.error = Some(e.to_string());
+                // The action may have failed at one of its prompts
+                self.renderer.enter_raw_mode()?;
+            }
         }
-        
-        self.renderer.exit_raw_mode()?;
         Ok(())
     }
 
     fn refresh_navigation(&mut self) -> Result<(), Box<dyn Error>> {
-        let tasks = self.task_service.load_hierarchy()?;
-        self.navigation = NavigationService::new(&tasks);
+        self.tasks = self.task_service.load_filtered_hierarchy(&self.filter)?;
+        Self::expand_revealed(&mut self.tasks, &self.revealed);
+        self.navigation = NavigationService::new(&self.tasks);
+        if self.navigation.get_task_with_depth(self.selected_id).is_none() {
+            self.selected_id = self.navigation.get_first_id().unwrap_or(-1);
+        }
+        if let Some(search) = &mut self.search {
+            search.hits = self.task_service.search(&search.query)?;
+            search.current = search.current.min(search.hits.len().saturating_sub(1));
+        }
         Ok(())
     }
 
+    fn expand_revealed(tasks: &mut [TaskWithKids], revealed: &HashSet<u32>) {
+        for node in tasks {
+            if revealed.contains(&node.task.id) {
+                node.task.collapsed = false;
+            }
+            Self::expand_revealed(&mut node.subtasks, revealed);
+        }
+    }
+
     fn render_main_view(&mut self) -> Result<(), Box<dyn Error>> {
         self.renderer.clear_screen()?;
         
-        queue!(std::io::stdout(), Print("Your tasks:\r\n\r\n".cyan()))?;
+        let mut header_height = 2;
+        queue!(std::io::stdout(), Print("Your tasks:\r\n".cyan()))?;
+        if !self.filter.is_empty() {
+            queue!(std::io::stdout(), Print(format!("Showing {}\r\n", self.filter).yellow()))?;
+            header_height += 1;
+        }
+        if let Some(search) = &self.search {
+            let status = match search.hits.get(search.current) {
+                Some(hit) => format!("{}/{}  {}", search.current + 1, search.hits.len(),
+                    TaskRenderer::format_marked(&hit.snippet, |text| text.dimmed())),
+                None => String::from("no matches"),
+            };
+            queue!(std::io::stdout(), Print(format!("{} {}\r\n",
+                format!("Search \"{}\":", search.query).yellow(), status)))?;
+            header_height += 1;
+        }
+        if let Some(notice) = self.notice.take() {
+            queue!(std::io::stdout(), Print(format!("{}\r\n", notice).green()))?;
+            header_height += 1;
+        }
+        if let Some(error) = self.error.take() {
+            queue!(std::io::stdout(), Print(format!("{}\r\n", error).red()))?;
+            header_height += 1;
+        }
+        queue!(std::io::stdout(), Print("\r\n"))?;
+
+        // Besides the header and menu, keep one row for the scroll indicator and
+        // one for the cursor line after the menu, so the screen never scrolls
+        let (_, rows) = terminal::size()?;
+        self.viewport.resize((rows as usize).saturating_sub(header_height + TaskRenderer::MAIN_MENU_HEIGHT + 2));
+        if let Some(index) = self.navigation.find_index_by_id(self.selected_id) {
+            self.viewport.scroll_to(index, self.navigation.len());
+        }
         
-        let tasks = self.task_service.load_hierarchy()?;
-        TaskRenderer::render_task_list(&tasks, self.selected_id)?;
+        let hits = self.search.as_ref().map(|search| search.hits.as_slice()).unwrap_or_default();
+        TaskRenderer::render_task_list(&self.tasks, self.selected_id, &self.viewport, hits)?;
         TaskRenderer::render_main_menu()?;
         
         self.renderer.flush()?;
         Ok(())
     }
 
-    fn handle_main_events(&mut self) -> Result<(), Box<dyn Error>> {
-        match InputHandler::read_key()? {
+    fn handle_key(&mut self, key: KeyEvent) -> Result<(), Box<dyn Error>> {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('r') if control => self.redo()?,
+            KeyCode::Up if shift => self.move_selected_task(MoveDirection::Up)?,
+            KeyCode::Down if shift => self.move_selected_task(MoveDirection::Down)?,
+            KeyCode::Char('K') => self.move_selected_task(MoveDirection::Up)?,
+            KeyCode::Char('J') => self.move_selected_task(MoveDirection::Down)?,
             KeyCode::Up => self.navigate_up()?,
             KeyCode::Down => self.navigate_down()?,
+            KeyCode::PageUp => self.scroll_selection(-(self.viewport.height() as isize)),
+            KeyCode::PageDown => self.scroll_selection(self.viewport.height() as isize),
+            KeyCode::Home => self.scroll_selection(isize::MIN),
+            KeyCode::End => self.scroll_selection(isize::MAX),
+            KeyCode::Left => self.collapse_or_select_parent()?,
+            KeyCode::Right => self.expand_selected_task()?,
             KeyCode::Enter => self.show_task_details()?,
             KeyCode::Tab => self.toggle_task_completion()?,
+            KeyCode::Char('p') => self.cycle_task_priority()?,
+            KeyCode::Char('>') => self.indent_selected_task()?,
+            KeyCode::Char('<') => self.outdent_selected_task()?,
+            KeyCode::Char('m') => self.move_selected_task_to()?,
+            KeyCode::Char('t') => self.edit_selected_tags()?,
+            KeyCode::Char('f') => self.edit_filter()?,
+            KeyCode::Char('c') => self.filter.cycle_completed(),
+            KeyCode::Char('/') => self.start_search()?,
+            KeyCode::Char('n') => self.jump_to_match(1)?,
+            KeyCode::Char('N') => self.jump_to_match(-1)?,
             KeyCode::Char('a') => self.add_task(None)?,
             KeyCode::Char('d') => self.delete_selected_task()?,
+            KeyCode::Char('u') => self.undo()?,
+            KeyCode::Char('T') => self.show_trash()?,
+            KeyCode::Char('x') => self.archive_selected_task()?,
+            KeyCode::Char('X') => self.archive_completed_tasks()?,
+            KeyCode::Char('A') => self.show_archive()?,
             KeyCode::Char('q') => self.running = false,
             _ => {}
         }
         Ok(())
     }
 
+    fn undo(&mut self) -> Result<(), Box<dyn Error>> {
+        self.notice = Some(match self.task_service.undo()? {
+            Some(label) => format!("Undone: {}", label),
+            None => String::from("Nothing to undo"),
+        });
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), Box<dyn Error>> {
+        self.notice = Some(match self.task_service.redo()? {
+            Some(label) => format!("Redone: {}", label),
+            None => String::from("Nothing to redo"),
+        });
+        Ok(())
+    }
+
     fn navigate_up(&mut self) -> Result<(), Box<dyn Error>> {
         if self.selected_id == -1 {
             return Ok(()); // No tasks for navigation
@@ -98,6 +251,97 @@ impl TodoTUI {
         Ok(())
     }
 
+    fn move_selected_task(&mut self, direction: MoveDirection) -> Result<(), Box<dyn Error>> {
+        if self.selected_id == -1 {
+            return Ok(()); // No task selected
+        }
+        self.task_service.move_task(self.selected_id as u32, direction)?;
+        Ok(())
+    }
+
+    /// ← collapses an expanded task with subtasks, otherwise jumps to its parent.
+    fn collapse_or_select_parent(&mut self) -> Result<(), Box<dyn Error>> {
+        let task = match self.navigation.get_task_with_depth(self.selected_id) {
+            Some((t, _)) => t,
+            None => return Ok(()) // No task selected
+        };
+        let has_subtasks = !self.task_service.get_children(task.id)?.is_empty();
+        if has_subtasks && !task.collapsed {
+            self.revealed.remove(&task.id);
+            self.task_service.set_collapsed(task.id, true)?;
+        } else if let Some(parent_id) = task.parent_id
+            && self.navigation.get_task_with_depth(parent_id as i32).is_some() {
+            self.selected_id = parent_id as i32;
+        }
+        Ok(())
+    }
+
+    fn expand_selected_task(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some((task, _)) = self.navigation.get_task_with_depth(self.selected_id)
+            && task.collapsed {
+            self.task_service.set_collapsed(task.id, false)?;
+        }
+        Ok(())
+    }
+
+    fn indent_selected_task(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.selected_id == -1 {
+            return Ok(()); // No task selected
+        }
+        self.task_service.indent_task(self.selected_id as u32)?;
+        Ok(())
+    }
+
+    fn outdent_selected_task(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.selected_id == -1 {
+            return Ok(()); // No task selected
+        }
+        self.task_service.outdent_task(self.selected_id as u32)?;
+        Ok(())
+    }
+
+    fn move_selected_task_to(&mut self) -> Result<(), Box<dyn Error>> {
+        let task = match self.navigation.get_task_with_depth(self.selected_id) {
+            Some((t, _)) => t,
+            None => return Ok(()) // No task selected
+        };
+        self.renderer.enter_interactive_mode()?;
+
+        println!("📦 Moving: {}\n", task.name.cyan());
+        println!("   0. (top level)");
+        // The task itself and its subtasks can't become the new parent
+        let targets = NavigationService::flatten_all(&self.task_service.load_hierarchy()?);
+        for flat in targets.iter().filter(|ft| !ft.parent_path.contains(&task.id)) {
+            println!("{:>4}. {}{}", flat.task.id, "  ".repeat(flat.depth as usize), flat.task.name);
+        }
+        println!();
+
+        let target = InputHandler::read_optional(
+            "Move under task id [0 = top level, Enter to cancel]: ",
+            |input| Ok(input.parse::<u32>()?),
+        )?;
+        if let Some(target) = target {
+            let new_parent = if target == 0 { None } else { Some(target) };
+            if let Err(e) = self.task_service.reparent_task(task.id, new_parent) {
+                println!("{}", e.to_string().red());
+                InputHandler::read_text("Press Enter to continue...")?;
+            }
+        }
+
+        self.renderer.enter_raw_mode()?;
+        Ok(())
+    }
+
+    /// Moves the selection by `rows`, stopping at the first and last task.
+    fn scroll_selection(&mut self, rows: isize) {
+        if let Some(index) = self.navigation.find_index_by_id(self.selected_id) {
+            let target = index.saturating_add_signed(rows);
+            if let Some(id) = self.navigation.get_id_at(target) {
+                self.selected_id = id;
+            }
+        }
+    }
+
     fn toggle_task_completion(&mut self) -> Result<(), Box<dyn Error>> {
         if self.selected_id == -1 {
             return Ok(()); // No task selected
@@ -106,6 +350,225 @@ impl TodoTUI {
         Ok(())
     }
 
+    fn cycle_task_priority(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.selected_id == -1 {
+            return Ok(()); // No task selected
+        }
+        self.task_service.cycle_task_priority(self.selected_id as u32)?;
+        Ok(())
+    }
+
+    fn edit_selected_tags(&mut self) -> Result<(), Box<dyn Error>> {
+        let task = match self.navigation.get_task_with_depth(self.selected_id) {
+            Some((t, _)) => t,
+            None => return Ok(()) // No task selected
+        };
+        self.renderer.enter_interactive_mode()?;
+
+        println!("🏷  Tags for: {}", task.name.cyan());
+        let current = if task.tags.is_empty() { String::from("none") } else { task.tags.join(" ") };
+        let tags = InputHandler::read_optional(
+            &format!("Tags [{}] (space separated, '-' to clear, Enter to keep): ", current),
+            |input| if input == "-" { Ok(Vec::new()) } else { Tag::parse_list(input) },
+        )?;
+        if let Some(tags) = tags {
+            self.task_service.set_task_tags(task.id, &tags)?;
+        }
+
+        self.renderer.enter_raw_mode()?;
+        Ok(())
+    }
+
+    fn edit_filter(&mut self) -> Result<(), Box<dyn Error>> {
+        self.renderer.enter_interactive_mode()?;
+
+        println!("🔎 Filter, e.g. {}", "status:open tag:work due<7d depth<=2 \"deploy\"".cyan());
+        println!("   Terms: status:open|done|all  tag:NAME  priority>=high  due<7d  due:overdue|none  depth<=N");
+        println!("   Words search names and descriptions; use OR, -term and (...) to combine.\n");
+        let tags = self.task_service.list_tags()?;
+        if !tags.is_empty() {
+            let names: Vec<String> = tags.iter().map(|tag| format!("{} ({})", tag.name, tag.task_count)).collect();
+            println!("🏷  Tags: {}\n", names.join("  "));
+        }
+
+        let current = self.filter.query.as_ref().map(Query::to_string).unwrap_or_default();
+        let prompt = if current.is_empty() {
+            String::from("Filter [Enter to show all]: ")
+        } else {
+            format!("Filter [{}] ('-' to clear, Enter to keep): ", current)
+        };
+        let query = InputHandler::read_optional(&prompt, |input| match input {
+            "-" => Ok(None),
+            _ => Query::parse(input).map(Some),
+        })?;
+        if let Some(query) = query {
+            self.filter.query = query;
+        }
+
+        self.renderer.enter_raw_mode()?;
+        Ok(())
+    }
+
+    fn start_search(&mut self) -> Result<(), Box<dyn Error>> {
+        self.renderer.enter_interactive_mode()?;
+        let query = InputHandler::read_text("🔍 Search names and descriptions [Enter to clear]: ")?;
+        self.renderer.enter_raw_mode()?;
+
+        self.revealed.clear();
+        if query.is_empty() {
+            self.search = None;
+            return Ok(());
+        }
+        let hits = self.task_service.search(&query)?;
+        self.search = Some(Search { query, hits, current: 0 });
+        self.jump_to_match(0)
+    }
+
+    /// Selects the match `step` places from the current one (wrapping around),
+    /// skipping matches hidden by the filter.
+    fn jump_to_match(&mut self, step: isize) -> Result<(), Box<dyn Error>> {
+        let Some(search) = &self.search else { return Ok(()) };
+        let ids: Vec<u32> = search.hits.iter().map(|hit| hit.task_id).collect();
+        let count = ids.len() as isize;
+        let direction = if step < 0 { -1 } else { 1 };
+        let mut index = search.current as isize + step;
+        for _ in 0..count {
+            index = index.rem_euclid(count);
+            if self.reveal_task(ids[index as usize])? {
+                if let Some(search) = &mut self.search {
+                    search.current = index as usize;
+                }
+                return Ok(());
+            }
+            index += direction;
+        }
+        Ok(())
+    }
+
+    /// Selects `task_id`, opening its collapsed ancestors until the search
+    /// changes. Returns false if the filter hides it.
+    fn reveal_task(&mut self, task_id: u32) -> Result<bool, Box<dyn Error>> {
+        let tasks = NavigationService::flatten_all(&self.tasks);
+        let Some(flat) = tasks.iter().find(|ft| ft.task.id == task_id) else {
+            return Ok(false);
+        };
+        self.revealed.extend(flat.parent_path.iter().filter(|&&id| id != task_id));
+        self.refresh_navigation()?;
+        self.selected_id = task_id as i32;
+        Ok(true)
+    }
+
+    /// Lists the trashed tasks, which can be restored or purged for good.
+    fn show_trash(&mut self) -> Result<(), Box<dyn Error>> {
+        self.renderer.enter_interactive_mode()?;
+
+        loop {
+            self.renderer.clear_screen()?;
+            let trash = self.task_service.load_trash()?;
+            match self.trash_retention_days {
+                0 => println!("🗑  Trash\n"),
+                days => println!("🗑  Trash (tasks are purged {} days after deletion)\n", days),
+            }
+            if trash.is_empty() {
+                println!("{}", "The trash is empty.\n".yellow());
+                InputHandler::read_text("Press Enter to continue...")?;
+                break;
+            }
+            for node in &trash {
+                let subtasks = NavigationService::flatten_all(&node.subtasks).len();
+                let subtasks = if subtasks > 0 { format!(" (+{} subtasks)", subtasks) } else { String::new() };
+                let deleted = node.task.deleted_at
+                    .map(|date| format!("  deleted {}", utc_to_local(date).format("%Y-%m-%d %H:%M")))
+                    .unwrap_or_default();
+                println!("{:>4}. {}{}{}", node.task.id, node.task.name.cyan(), subtasks, deleted.dimmed());
+            }
+            println!();
+
+            let action = InputHandler::read_optional(
+                "Task id to restore, 'p ID' to purge it, 'e' to empty the trash [Enter to return]: ",
+                TrashAction::parse,
+            )?;
+            let result = match action {
+                None => break,
+                Some(TrashAction::Restore(id)) => self.task_service.restore_task(id),
+                Some(TrashAction::Purge(id)) => self.task_service.purge_task(id),
+                Some(TrashAction::Empty) => {
+                    let answer = InputHandler::read_text("Delete everything in the trash for good? [y/N]: ")?;
+                    if answer.eq_ignore_ascii_case("y") {
+                        self.task_service.empty_trash().map(|_| ())
+                    } else {
+                        Ok(())
+                    }
+                }
+            };
+            if let Err(e) = result {
+                println!("{}", e.to_string().red());
+                InputHandler::read_text("Press Enter to continue...")?;
+            }
+        }
+
+        self.renderer.enter_raw_mode()?;
+        Ok(())
+    }
+
+    fn archive_selected_task(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.selected_id == -1 {
+            return Ok(()); // No task selected
+        }
+        self.task_service.archive_task(self.selected_id as u32)?;
+        self.notice = Some(String::from("Task archived, press [A] to browse the archive"));
+        Ok(())
+    }
+
+    fn archive_completed_tasks(&mut self) -> Result<(), Box<dyn Error>> {
+        self.notice = Some(match self.task_service.archive_completed_roots()? {
+            0 => String::from("No completed top level tasks to archive"),
+            1 => String::from("Archived 1 task"),
+            count => format!("Archived {} tasks", count),
+        });
+        Ok(())
+    }
+
+    /// Lists the archived trees read-only; a whole tree can be unarchived.
+    fn show_archive(&mut self) -> Result<(), Box<dyn Error>> {
+        self.renderer.enter_interactive_mode()?;
+
+        loop {
+            self.renderer.clear_screen()?;
+            let archive = self.task_service.load_archive()?;
+            println!("📦 Archive\n");
+            if archive.is_empty() {
+                println!("{}", "The archive is empty.\n".yellow());
+                InputHandler::read_text("Press Enter to continue...")?;
+                break;
+            }
+            for node in &archive {
+                let archived = node.task.archived_at
+                    .map(|date| format!("  archived {}", utc_to_local(date).format("%Y-%m-%d")))
+                    .unwrap_or_default();
+                println!("{:>4}. {}{}", node.task.id, node.task.name.cyan(), archived.dimmed());
+                for flat in NavigationService::flatten_all(&node.subtasks) {
+                    let status = if flat.task.completed { "✓" } else { "○" };
+                    println!("      {}{} {}", "  ".repeat(flat.depth as usize + 1), status, flat.task.name);
+                }
+            }
+            println!();
+
+            let task_id = InputHandler::read_optional(
+                "Task id to unarchive [Enter to return]: ",
+                |input| Ok(input.parse::<u32>()?),
+            )?;
+            let Some(task_id) = task_id else { break };
+            if let Err(e) = self.task_service.unarchive_task(task_id) {
+                println!("{}", e.to_string().red());
+                InputHandler::read_text("Press Enter to continue...")?;
+            }
+        }
+
+        self.renderer.enter_raw_mode()?;
+        Ok(())
+    }
+
     fn show_task_details(&mut self) -> Result<(), Box<dyn Error>> {
         if self.selected_id == -1 {
             return Ok(()); // No task selected
@@ -118,7 +581,8 @@ impl TodoTUI {
                 None => return Ok(())
             };
             let children = self.task_service.get_children(task.id)?;
-            TaskRenderer::render_task_details(&task, &children)?;
+            let history = self.task_service.history(task.id)?;
+            TaskRenderer::render_task_details(&task, &children, &history)?;
             TaskRenderer::render_task_detail_menu()?;
             
             let choice = InputHandler::read_choice("Your choice: ")?;
@@ -174,8 +638,27 @@ impl TodoTUI {
         
         let desc = InputHandler::read_text("Enter task description [Enter to skip]: ")?;
         let description = if desc.is_empty() { None } else { Some(desc) };
+
+        let due_date = InputHandler::read_optional(
+            "Enter due date YYYY-MM-DD [HH:MM] [Enter to skip]: ",
+            DueDate::parse,
+        )?;
+        let priority = InputHandler::read_optional(
+            "Enter priority none/low/medium/high/urgent [Enter to skip]: ",
+            Priority::parse,
+        )?.unwrap_or_default();
+        let tags = InputHandler::read_optional(
+            "Enter tags, space separated [Enter to skip]: ",
+            Tag::parse_list,
+        )?.unwrap_or_default();
+        let recurrence = InputHandler::read_optional(
+            "Repeat daily / weekly [mon,wed] / monthly N / every N days [Enter to skip]: ",
+            Recurrence::parse,
+        )?;
         
-        let insert_id = self.task_service.create_task(name, parent_id, description)?;
+        let insert_id = self.task_service.add_task(CreateTask {
+            name, parent_id, description, due_date, priority, recurrence, ..Default::default()
+        }, &tags)?;
         
         self.renderer.clear_screen()?;
         println!("{}", "Task added!\n".green());
@@ -194,12 +677,30 @@ impl TodoTUI {
         println!("Enter new data for task:");
 
         let name = InputHandler::read_text(&format!("Name [{}]: ", task.name))?;
-        let description = InputHandler::read_text("Description [Enter to skip]: ")?;
+        let description = InputHandler::read_optional(
+            "Description ('-' to clear, Enter to skip): ",
+            |input| Ok(if input == "-" { None } else { Some(input.to_string()) }),
+        )?;
+        let current_due = task.due_date.map(|d| d.to_string()).unwrap_or(String::from("none"));
+        let due_date = InputHandler::read_optional(
+            &format!("Due date [{}] (YYYY-MM-DD [HH:MM], '-' to clear, Enter to skip): ", current_due),
+            |input| if input == "-" { Ok(None) } else { DueDate::parse(input).map(Some) },
+        )?;
+        let priority = InputHandler::read_optional(
+            &format!("Priority [{}] (none/low/medium/high/urgent, Enter to skip): ", task.priority),
+            Priority::parse,
+        )?;
+        let current_recurrence = task.recurrence.as_ref().map(|r| r.to_string()).unwrap_or(String::from("never"));
+        let recurrence = InputHandler::read_optional(
+            &format!("Repeat [{}] (daily / weekly [mon,wed] / monthly N / every N days, '-' to stop, Enter to skip): ",
+                current_recurrence),
+            |input| if input == "-" { Ok(None) } else { Recurrence::parse(input).map(Some) },
+        )?;
         
         let name_final = if name.is_empty() { None } else { Some(name) };
-        let desc_final = if description.is_empty() { None } else { Some(description) };
         
-        let updated = self.task_service.update_task(task.id, name_final, desc_final)?;
+        let updated = self.task_service.update_task(
+            task.id, name_final, description, due_date, priority, recurrence)?;
 
         InputHandler::read_text("Press Enter to continue...")?;
 
@@ -225,10 +726,10 @@ impl TodoTUI {
                 self.selected_id = self.navigation.get_first_id().unwrap_or(-1);
                 
                 self.renderer.clear_screen()?;
-                println!("{}", "Task deleted!\n".red());
+                println!("{}", "Task moved to the trash! Press [u] to undo or [T] to open the trash.\n".red());
             } else {
                 self.renderer.clear_screen()?;
-                println!("{}", "Deletion cancelled\n");
+                println!("Deletion cancelled\n");
             }
 
             // Wait for Enter to continue
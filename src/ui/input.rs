@@ -1,15 +1,21 @@
 use std::{error::Error, io::{BufRead, Write}};
 
-use crossterm::event::{Event, KeyCode, read};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers, read};
 use colored::Colorize;
 
+pub enum InputEvent {
+    Key(KeyEvent),
+    Resize,
+}
+
 pub struct InputHandler;
 
 impl InputHandler {
-    pub fn read_key() -> Result<KeyCode, Box<dyn Error>> {
+    pub fn read_event() -> Result<InputEvent, Box<dyn Error>> {
         match read()? {
-            Event::Key(key_event) => Ok(key_event.code),
-            _ => Ok(KeyCode::Null),
+            Event::Key(key_event) => Ok(InputEvent::Key(key_event)),
+            Event::Resize(_, _) => Ok(InputEvent::Resize),
+            _ => Ok(InputEvent::Key(KeyEvent::new(KeyCode::Null, KeyModifiers::NONE))),
         }
     }
     
@@ -22,6 +28,23 @@ impl InputHandler {
         Ok(input.trim().to_string())
     }
     
+    /// Reads a value with `parse`, re-prompting on invalid input. Empty input yields `None`.
+    pub fn read_optional<T>(
+        prompt: &str,
+        parse: impl Fn(&str) -> Result<T, Box<dyn Error>>,
+    ) -> Result<Option<T>, Box<dyn Error>> {
+        loop {
+            let input = Self::read_text(prompt)?;
+            if input.is_empty() {
+                return Ok(None);
+            }
+            match parse(&input) {
+                Ok(value) => return Ok(Some(value)),
+                Err(e) => println!("{}", e.to_string().yellow()),
+            }
+        }
+    }
+
     pub fn read_choice(prompt: &str) -> Result<u32, Box<dyn Error>> {
         loop {
             let input = Self::read_text(prompt)?;
@@ -35,7 +58,7 @@ impl InputHandler {
     pub fn confirm_deletion(task_name: &str) -> Result<bool, Box<dyn Error>> {
         loop {
             println!("\n🗑️ Deleting task: {}", task_name.red());
-            println!("Are you sure you want to delete this task (all subtasks will also be deleted)?");
+            println!("Are you sure you want to move this task and all its subtasks to the trash?");
             println!("{}", "1. Yes".red());
             println!("2. No");
 
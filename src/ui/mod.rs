@@ -1,3 +1,4 @@
 pub mod terminal;
 pub mod input;
-pub mod task_renderer;
\ No newline at end of file
+pub mod task_renderer;
+pub mod viewport;
\ No newline at end of file
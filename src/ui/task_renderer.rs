@@ -1,73 +1,181 @@
 use std::error::Error;
 
-use colored::Colorize;
+use chrono::{Local, NaiveDateTime};
+use colored::{ColoredString, Colorize};
 use crossterm::{
     queue, style::Print
 };
 
-use crate::task::*;
+use crate::{task::*, ui::viewport::Viewport};
 
 pub struct TaskRenderer;
 
 impl TaskRenderer {
+    /// Lines printed by `render_main_menu`.
+    pub const MAIN_MENU_HEIGHT: usize = 7;
+    /// Most recent history entries shown in the task details.
+    pub const HISTORY_LINES: usize = 10;
+
+    /// Prints the rows inside `viewport`, padded to its full height, followed by
+    /// a one line scroll indicator.
     pub fn render_task_list(
         tasks: &[TaskWithKids], 
-        selected_id: i32
+        selected_id: i32,
+        viewport: &Viewport,
+        hits: &[SearchHit],
     ) -> Result<(), Box<dyn Error>> {
         if tasks.is_empty() {
             queue!(std::io::stdout(), 
                 Print("No tasks\r\n".red()),
                 Print("Press [a] to add a task\r\n\r\n"),
             )?;
-        } else {
-            for task in tasks {
-                Self::render_task_tree(task, 0, selected_id)?;
-            }
+            return Ok(());
         }
+
+        let now = Local::now().naive_local();
+        let mut rows = Vec::new();
+        for task in tasks {
+            Self::render_task_tree(task, 0, selected_id, now, hits, &mut rows);
+        }
+
+        let visible = viewport.visible_range(rows.len());
+        for row in &rows[visible.clone()] {
+            queue!(std::io::stdout(), Print(format!("{}\r\n", row)))?;
+        }
+        for _ in visible.len()..viewport.height() {
+            queue!(std::io::stdout(), Print("\r\n"))?;
+        }
+
+        let indicator = if rows.len() > visible.len() {
+            let up = if visible.start > 0 { "▲" } else { " " };
+            let down = if visible.end < rows.len() { "▼" } else { " " };
+            format!("{}{} {}-{} of {}", up, down, visible.start + 1, visible.end, rows.len()).dimmed().to_string()
+        } else {
+            String::new()
+        };
+        queue!(std::io::stdout(), Print(format!("{}\r\n", indicator)))?;
         Ok(())
     }
     
     fn render_task_tree(
         task: &TaskWithKids, 
         depth: u32, 
-        selected_id: i32
-    ) -> Result<(), Box<dyn Error>> {
+        selected_id: i32,
+        now: NaiveDateTime,
+        hits: &[SearchHit],
+        rows: &mut Vec<String>,
+    ) {
         let status_char = if task.task.completed { 
             "✓".green() 
         } else { 
             "○".white() 
         };
         
-        let name = if task.task.completed { 
-            task.task.name.strikethrough() 
-        } else { 
-            task.task.name.white() 
+        let style = |text: &str| {
+            let text = if task.task.completed { text.strikethrough() } else { text.white() };
+            if task.task.id as i32 == selected_id { text.green().bold() } else { text }
+        };
+        let name_colored = match hits.iter().find(|hit| hit.task_id == task.task.id) {
+            Some(hit) if hit.name_matches() => Self::format_marked(&hit.name, style),
+            Some(_) => format!("{} {}", style(&task.task.name), "(in description)".yellow().dimmed()),
+            None => style(&task.task.name).to_string(),
+        };
+        
+        let due = match task.task.due_date {
+            Some(due) if !task.task.completed => format!(" {}", Self::format_due(due, now)),
+            _ => String::new(),
         };
         
-        let name_colored = if task.task.id as i32 == selected_id {
-            name.green().bold()
+        let priority = match task.task.priority {
+            Priority::None => String::new(),
+            priority => format!("{} ", Self::priority_marker(priority)),
+        };
+        
+        let repeat = if task.task.recurrence.is_some() { " ↻".blue().to_string() } else { String::new() };
+        let tags: String = task.task.tags.iter()
+            .map(|tag| format!(" {}", Self::tag_chip(tag)))
+            .collect();
+        
+        let hidden = task.count_descendants();
+        let folded = if task.task.collapsed && hidden > 0 {
+            format!(" {}", format!("▸ ({} hidden)", hidden).dimmed())
         } else {
-            name
+            String::new()
         };
         
         let indent = "  ".repeat(depth as usize);
-        queue!(std::io::stdout(), 
-            Print(format!("{} {} {}\r\n", indent, status_char, name_colored))
-        )?;
+        rows.push(format!("{} {} {}{}{}{}{}{}",
+            indent, status_char, priority, name_colored, repeat, tags, due, folded));
         
+        if task.task.collapsed {
+            return;
+        }
         for subtask in &task.subtasks {
-            Self::render_task_tree(subtask, depth + 1, selected_id)?;
+            Self::render_task_tree(subtask, depth + 1, selected_id, now, hits, rows);
         }
-        
-        Ok(())
     }
     
-    pub fn render_task_details(task: &Task, children: &[Task]) -> Result<(), Box<dyn Error>> {
+    /// Renders text marked by a search with the matches highlighted and the
+    /// rest styled by `style`.
+    pub fn format_marked(text: &str, style: impl Fn(&str) -> ColoredString) -> String {
+        SearchHit::segments(text).into_iter()
+            .map(|(part, matched)| match matched {
+                true => part.black().on_yellow().to_string(),
+                false => style(part).to_string(),
+            })
+            .collect()
+    }
+
+    fn tag_chip(tag: &str) -> ColoredString {
+        format!("[{}]", tag).cyan()
+    }
+
+    fn priority_marker(priority: Priority) -> ColoredString {
+        match priority {
+            Priority::None => "".normal(),
+            Priority::Low => "↓".blue(),
+            Priority::Medium => "!".yellow(),
+            Priority::High => "!!".red(),
+            Priority::Urgent => "!!!".red().bold(),
+        }
+    }
+
+    fn format_due(due: DueDate, now: NaiveDateTime) -> ColoredString {
+        match due.status(now) {
+            DueStatus::Overdue => format!("(overdue {})", due).red().bold(),
+            DueStatus::Today => format!("(due today {})", due).yellow(),
+            DueStatus::Upcoming => format!("(due {})", due).dimmed(),
+        }
+    }
+    
+    pub fn render_task_details(task: &Task, children: &[Task], history: &[TaskEvent]) -> Result<(), Box<dyn Error>> {
         println!("📋 Task Details");
         println!("{}", "─".repeat(30));
         println!("📝 Name: {}", task.name);
-        println!("✅ Status: {}", if task.completed { "Completed" } else { "In Progress" });
-        println!("📅 Created: {}", task.creation_date.format("%Y-%m-%d %H:%M"));
+        let status = match (task.completed, task.completed_at) {
+            (true, Some(completed_at)) => format!("Completed on {}", utc_to_local(completed_at).format("%Y-%m-%d %H:%M")),
+            (true, None) => String::from("Completed"),
+            (false, _) => String::from("In Progress"),
+        };
+        println!("✅ Status: {}", status);
+        println!("⚑ Priority: {} {}", task.priority, Self::priority_marker(task.priority));
+        println!("📅 Created: {}", utc_to_local(task.creation_date).format("%Y-%m-%d %H:%M"));
+        if let Some(due) = task.due_date {
+            let due_text = if task.completed {
+                due.to_string().normal()
+            } else {
+                Self::format_due(due, Local::now().naive_local())
+            };
+            println!("⏰ Due: {}", due_text);
+        }
+
+        if let Some(recurrence) = &task.recurrence {
+            println!("🔁 Repeats: {}", recurrence);
+        }
+        if !task.tags.is_empty() {
+            let chips: Vec<String> = task.tags.iter().map(|tag| Self::tag_chip(tag).to_string()).collect();
+            println!("🏷  Tags: {}", chips.join(" "));
+        }
 
         if let Some(desc) = &task.description {
             println!("📄 Description: {}", desc);
@@ -80,6 +188,17 @@ impl TaskRenderer {
                 println!("  {}. {} {}", i + 1, status, child.name);
             }
         }
+
+        if !history.is_empty() {
+            println!("\n🕘 History:");
+            let skipped = history.len().saturating_sub(Self::HISTORY_LINES);
+            if skipped > 0 {
+                println!("  {}", format!("… {} earlier changes", skipped).dimmed());
+            }
+            for event in &history[skipped..] {
+                println!("  {}  {}", utc_to_local(event.created_at).format("%Y-%m-%d %H:%M").to_string().dimmed(), event);
+            }
+        }
         Ok(())
     }
     
@@ -87,11 +206,14 @@ impl TaskRenderer {
         queue!(std::io::stdout(),
             Print("\r\n"),
             Print("Controls:\r\n"),
-            Print("↑↓    Navigate\r\n"),
-            Print("Enter Task details\r\n"),
-            Print("a     Add task\r\n".green()),
-            Print("d     Delete task\r\n".red()),
-            Print("q     Quit\r\n"),
+            Print("↑↓ Navigate  PgUp/PgDn/Home/End Scroll  ←→ Collapse/expand\r\n"),
+            Print("⇧↑⇧↓ or K/J Move  > < Indent/outdent  m Move to...  u/^R Undo/redo\r\n"),
+            Print("Enter Details  Tab Toggle done  p Priority  t Tags  f Filter  / Search\r\n"),
+            Print("a Add task".green()),
+            Print("  "),
+            Print("d Delete task".red()),
+            Print("  c All/open/done  n/N Next/previous match  q Quit\r\n"),
+            Print("x Archive done task  X Archive all done  A Archive  T Trash\r\n"),
         )?;
         Ok(())
     }
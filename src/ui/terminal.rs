@@ -8,6 +8,12 @@ pub struct TerminalRenderer {
     stdout: std::io::Stdout,
 }
 
+impl Default for TerminalRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TerminalRenderer {
     pub fn new() -> Self {
         Self { stdout: std::io::stdout() }
@@ -52,7 +58,7 @@ impl TerminalRenderer {
     }
 
     pub fn print(&mut self, text: &str) -> Result<(), Box<dyn Error>> {
-        queue!(self.stdout, Print(format!("{}", text)))?;
+        queue!(self.stdout, Print(text.to_string()))?;
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,80 @@
+use std::ops::Range;
+
+/// The window of list rows that fits on screen.
+pub struct Viewport {
+    offset: usize,
+    height: usize,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Viewport {
+    pub fn new() -> Self {
+        Self { offset: 0, height: 1 }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn resize(&mut self, height: usize) {
+        self.height = height.max(1);
+    }
+
+    /// Scrolls as little as possible so that row `index` is visible,
+    /// and never past the end of a list of `total` rows.
+    pub fn scroll_to(&mut self, index: usize, total: usize) {
+        if index < self.offset {
+            self.offset = index;
+        } else if index >= self.offset + self.height {
+            self.offset = index + 1 - self.height;
+        }
+        self.offset = self.offset.min(total.saturating_sub(self.height));
+    }
+
+    pub fn visible_range(&self, total: usize) -> Range<usize> {
+        let start = self.offset.min(total);
+        start..(start + self.height).min(total)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection_stays_visible() {
+        let mut viewport = Viewport::new();
+        viewport.resize(5);
+
+        viewport.scroll_to(3, 20);
+        assert_eq!(viewport.visible_range(20), 0..5);
+
+        viewport.scroll_to(7, 20);
+        assert_eq!(viewport.visible_range(20), 3..8);
+
+        viewport.scroll_to(19, 20);
+        assert_eq!(viewport.visible_range(20), 15..20);
+
+        viewport.scroll_to(16, 20);
+        assert_eq!(viewport.visible_range(20), 15..20);
+
+        viewport.scroll_to(0, 20);
+        assert_eq!(viewport.visible_range(20), 0..5);
+    }
+
+    #[test]
+    fn shrinking_list_pulls_offset_back() {
+        let mut viewport = Viewport::new();
+        viewport.resize(5);
+        viewport.scroll_to(19, 20);
+
+        viewport.scroll_to(2, 3);
+        assert_eq!(viewport.visible_range(3), 0..3);
+    }
+}